@@ -2,9 +2,9 @@ use askama::Template;
 use axum::{
     Router,
     body::Body,
-    extract::Request,
+    extract::{DefaultBodyLimit, Request},
     http::{
-        HeaderValue, Method, Response, StatusCode,
+        HeaderName, HeaderValue, Method, Response, StatusCode,
         header::{self, CACHE_CONTROL, CONTENT_SECURITY_POLICY},
     },
     middleware::from_fn_with_state,
@@ -13,12 +13,18 @@ use axum::{
 };
 use axum_extra::routing::RouterExt;
 use petring::{
-    IoResult,
+    IoResult, ad_images,
     api::{
-        protected::{self, petads, petring as petring_protected},
+        protected::{
+            self, audit_log, bans, health as health_protected, moderation, petads,
+            petring as petring_protected, rings, url_rules,
+        },
         public,
     },
+    audit::ACTOR_HEADER,
+    communities::Communities,
     config::{Level, string_to_ip},
+    health, rotation,
     state::AppState,
 };
 use std::{
@@ -89,32 +95,7 @@ pub async fn render_404(_req: Request) -> Result<Response<Body>, Infallible> {
     Ok(HtmlTemplate(not_found).into_response())
 }
 
-#[tokio::main]
-async fn main() -> IoResult<()> {
-    let formatter =
-        debug_fn(|writer, field, value| write!(writer, "{field}: {value:?}")).delimited(",");
-
-    let config = match Config::load() {
-        Ok(config) => config,
-        Err(e) => {
-            panic!("Failed to load config: {e}");
-        }
-    };
-
-    let level: Level = config.logging().level.clone().into();
-
-    rustls::crypto::ring::default_provider()
-        .install_default()
-        .expect("Failed to install rustls crypto provider");
-
-    Subscriber::builder()
-        .with_max_level(level.0)
-        .fmt_fields(formatter)
-        .with_ansi(true)
-        .init();
-
-    let state = AppState::new().await;
-    let compression_predicate = SizeAbove::new(256).and(NotForContentType::IMAGES);
+fn community_router(state: AppState) -> Router {
     let cors_public = if cfg!(debug_assertions) {
         CorsLayer::new()
             .allow_origin(AllowOrigin::any())
@@ -144,7 +125,12 @@ async fn main() -> IoResult<()> {
             Method::DELETE,
             Method::HEAD,
         ])
-        .allow_headers([header::ACCEPT, header::CONTENT_TYPE, header::AUTHORIZATION])
+        .allow_headers([
+            header::ACCEPT,
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            HeaderName::from_static(ACTOR_HEADER),
+        ])
         .max_age(Duration::from_secs(60 * 60 * 24 * 7));
 
     let user_routes = Router::new()
@@ -152,9 +138,37 @@ async fn main() -> IoResult<()> {
         .route_with_tsr("/get/user/{username}/next", get(public::get_user_next))
         .route_with_tsr("/get/user/{username}/prev", get(public::get_user_prev))
         .route_with_tsr("/get/user/{username}/random", get(public::get_user_random))
+        .route_with_tsr(
+            "/get/user/{username}/neighbors",
+            get(public::get_user_neighbors),
+        )
+        .route_with_tsr("/embed/{username}", get(public::get_embed))
+        .route_with_tsr("/embed/buttons", get(public::get_buttons_embed))
+        .route("/widget.js", get(public::get_widget_js))
+        .route_with_tsr("/ring/{ring}/get/user/{username}", get(public::get_user))
+        .route_with_tsr(
+            "/ring/{ring}/get/user/{username}/next",
+            get(public::get_user_next),
+        )
+        .route_with_tsr(
+            "/ring/{ring}/get/user/{username}/prev",
+            get(public::get_user_prev),
+        )
+        .route_with_tsr(
+            "/ring/{ring}/get/user/{username}/random",
+            get(public::get_user_random),
+        )
+        .route_with_tsr(
+            "/ring/{ring}/get/user/{username}/neighbors",
+            get(public::get_user_neighbors),
+        )
+        .route_with_tsr("/ring/{ring}/embed/{username}", get(public::get_embed))
+        .route_with_tsr("/ring/{ring}/embed/buttons", get(public::get_buttons_embed))
+        .route("/ring/{ring}/widget.js", get(public::get_widget_js))
         .layer(cors_public.clone());
 
     let protected_routes = Router::new()
+        .route_with_tsr("/get/users/all", get(petring_protected::get_users))
         .route_with_tsr(
             "/get/user/by-discord/{discord_id}",
             get(petring_protected::get_user_by_discord_id),
@@ -183,16 +197,74 @@ async fn main() -> IoResult<()> {
             "/patch/user/verify/{discord_user_id}",
             patch(petring_protected::patch_user_verify),
         )
+        .route_with_tsr("/patch/user/reject", patch(moderation::patch_user_reject))
+        .route_with_tsr("/patch/user/suspend", patch(moderation::patch_user_suspend))
+        .route_with_tsr("/get/queue", get(moderation::get_queue))
+        .route_with_tsr("/get/audit", get(audit_log::get_audit))
+        .route_with_tsr("/get/bans", get(bans::get_bans))
+        .route_with_tsr("/post/ban", post(bans::post_ban))
+        .route_with_tsr("/patch/ban/{id}", patch(bans::patch_ban))
+        .route_with_tsr("/delete/ban/{id}", delete(bans::delete_ban))
+        .route_with_tsr("/get/url-policy", get(url_rules::get_url_policy))
+        .route_with_tsr("/get/url-policy/check", get(url_rules::get_url_check))
+        .route_with_tsr("/post/url-rule", post(url_rules::post_url_rule))
+        .route_with_tsr("/delete/url-rule/{id}", delete(url_rules::delete_url_rule))
+        .route_with_tsr(
+            "/patch/user/backlinks/{discord_id}",
+            patch(petring_protected::patch_user_backlinks),
+        )
+        .route_with_tsr(
+            "/patch/user/visibility",
+            patch(petring_protected::patch_user_visibility),
+        )
         .route_with_tsr(
             "/post/user/submit",
             post(petring_protected::post_user_submit),
         )
+        .route_with_tsr("/get/health", get(health_protected::get_link_health))
+        .route_with_tsr(
+            "/get/health/by-discord/{discord_id}",
+            get(health_protected::get_link_health_by_discord_id),
+        )
+        .route_with_tsr("/get/ring/order", get(petring_protected::get_ring_order))
+        .route_with_tsr(
+            "/patch/ring/order",
+            patch(petring_protected::patch_ring_order),
+        )
+        .route_with_tsr(
+            "/patch/ring/move",
+            patch(petring_protected::patch_ring_move),
+        )
+        .route_with_tsr(
+            "/patch/ring/swap",
+            patch(petring_protected::patch_ring_swap),
+        )
+        .route_with_tsr("/get/ring/{ring}", get(rings::get_ring))
+        .route_with_tsr("/post/ring/create", post(rings::post_ring_create))
+        .route_with_tsr("/delete/ring/{ring}", delete(rings::delete_ring))
+        .route_with_tsr("/post/ring/{ring}/members", post(rings::post_ring_members))
+        .route_with_tsr(
+            "/delete/ring/{ring}/members",
+            delete(rings::delete_ring_members),
+        )
         .route_with_tsr("/post/ad/submit", post(petads::post_ad_submit))
+        .route_with_tsr(
+            "/post/ad/upload",
+            // Room for the multipart framing on top of the image itself
+            post(petads::post_ad_upload)
+                .layer(DefaultBodyLimit::max(state.ad_images.max_bytes + 64 * 1024)),
+        )
         .route_with_tsr(
             "/patch/ad/verify/{discord_user_id}",
             patch(petads::patch_ad_verify),
         )
+        .route_with_tsr("/patch/ad/reject", patch(moderation::patch_ad_reject))
+        .route_with_tsr("/patch/ad/suspend", patch(moderation::patch_ad_suspend))
         .route_with_tsr("/patch/ad/edit", patch(petads::patch_ad_edit))
+        .route_with_tsr("/patch/ad/weight", patch(petads::patch_ad_weight))
+        .route_with_tsr("/patch/ad/schedule", patch(petads::patch_ad_schedule))
+        .route_with_tsr("/get/ads/expiring", get(petads::get_expiring_ads))
+        .route_with_tsr("/get/ad/{discord_id}/stats", get(petads::get_ad_stats))
         .route_with_tsr(
             "/delete/ad/by-discord/{discord_id}",
             delete(petads::delete_ad_by_discord_id),
@@ -216,10 +288,27 @@ async fn main() -> IoResult<()> {
         .route_with_tsr("/get/uptime", get(public::get_uptime))
         .route_with_tsr("/get/users", get(public::get_all_users))
         .route_with_tsr("/get/users/random", get(public::get_random_user))
+        .route_with_tsr("/get/users/search", get(public::get_users_search))
+        .route_with_tsr("/get/rings", get(public::get_rings))
+        .route_with_tsr("/ring/{ring}/get/users", get(public::get_all_users))
+        .route_with_tsr(
+            "/ring/{ring}/get/users/random",
+            get(public::get_random_user),
+        )
+        .route_with_tsr(
+            "/ring/{ring}/get/users/search",
+            get(public::get_users_search),
+        )
+        .route_with_tsr("/get/buttons", get(public::get_buttons))
+        .route_with_tsr("/ring/{ring}/get/buttons", get(public::get_buttons))
+        .route_with_tsr("/buttons", get(public::get_buttons_page))
+        .route_with_tsr("/ring/{ring}/buttons", get(public::get_buttons_page))
         .route_with_tsr("/get/random-ad", get(public::get_random_ad))
+        .route("/ads/img/{hash}", get(public::get_ad_image))
+        .route("/ad/{id}/click", get(public::get_ad_click))
         .layer(cors_public.clone());
 
-    let app = Router::new()
+    Router::new()
         .fallback_service(service_fn(render_404))
         .layer(
             ServiceBuilder::new()
@@ -237,6 +326,65 @@ async fn main() -> IoResult<()> {
         .merge(protected_routes)
         .merge(user_routes)
         .merge(bot_routes)
+        .with_state(state)
+}
+
+#[tokio::main]
+async fn main() -> IoResult<()> {
+    let formatter =
+        debug_fn(|writer, field, value| write!(writer, "{field}: {value:?}")).delimited(",");
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            panic!("Failed to load config: {e}");
+        }
+    };
+
+    let level: Level = config.logging().level.clone().into();
+
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .expect("Failed to install rustls crypto provider");
+
+    Subscriber::builder()
+        .with_max_level(level.0)
+        .fmt_fields(formatter)
+        .with_ansi(true)
+        .init();
+
+    let default_state = AppState::new(&config, config.community()).await;
+    health::spawn_health_checker(default_state.clone());
+    rotation::spawn_flusher(default_state.clone());
+    ad_images::spawn_backfill(default_state.clone());
+
+    let mut states = vec![default_state.clone()];
+    let mut others = Vec::new();
+    for community in config.communities() {
+        let state = AppState::new(&config, community).await;
+        health::spawn_health_checker(state.clone());
+        rotation::spawn_flusher(state.clone());
+        ad_images::spawn_backfill(state.clone());
+        states.push(state.clone());
+        info!(
+            "Hosting community {} under /t/{}",
+            community.name, community.slug
+        );
+        others.push((community.clone(), community_router(state)));
+    }
+
+    ad_images::spawn_gc(states);
+
+    let communities = Communities::new(
+        (config.community().clone(), community_router(default_state)),
+        others,
+    );
+
+    let compression_predicate = SizeAbove::new(256).and(NotForContentType::IMAGES);
+    let app = Router::new()
+        .fallback_service(service_fn(move |request: Request| {
+            communities.clone().dispatch(request)
+        }))
         .layer(
             ServiceBuilder::new()
                 .layer(
@@ -256,8 +404,7 @@ async fn main() -> IoResult<()> {
                         .quality(CompressionLevel::Fastest)
                         .compress_when(compression_predicate),
                 ),
-        )
-        .with_state(state);
+        );
     //
     // This adds compression and decompression to the request and response
     // body streams, don't remove it!
@@ -1,8 +1,22 @@
+pub(crate) mod ad_images;
+pub(crate) mod ad_stats;
 pub(crate) mod api;
+pub(crate) mod audit;
+pub(crate) mod backlinks;
+pub(crate) mod bans;
+pub(crate) mod buttons;
+pub(crate) mod communities;
 pub(crate) mod config;
 pub(crate) mod database;
+pub(crate) mod directory;
+pub(crate) mod health;
 pub(crate) mod jwt;
+pub(crate) mod ring;
+pub(crate) mod rotation;
+pub(crate) mod search;
 pub(crate) mod state;
+pub(crate) mod url_policy;
+pub(crate) mod username;
 
 pub(crate) type IoResult<T> = std::io::Result<T>;
 pub(crate) type PetRingError = Box<dyn std::error::Error>;
@@ -0,0 +1,426 @@
+use super::{
+    api::AdResponse,
+    audit::{self, Audit},
+    database::{
+        AdFormat, Status, UrlKind, ads,
+        entities::{AdModel, Ads},
+    },
+    state::AppState,
+    url_policy,
+};
+use chrono::Utc;
+use imagesize::ImageType;
+use reqwest::{StatusCode, Url, header::CONTENT_TYPE};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, Iterable, QueryFilter, QuerySelect, Set,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fmt,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+#[allow(unused_imports)]
+use tracing::{debug, error, info, warn};
+
+/* Ad images
+ *
+ * Ads used to hotlink their image, which broke whenever the host removed
+ * it and let people swap the picture after approval. Now the image is
+ * downloaded once, checked, and stored under the sha256 of its contents.
+ * The same bytes always end up in the same file, so nothing is stored twice
+ * and the files can be cached forever.
+ *
+ * Files nobody points at anymore are cleaned up now and then. Communities
+ * share the directory, so that looks at every community's ads at once.
+ */
+
+// How often unused images are looked for
+const GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// Files are written before the ad that uses them, so new ones are left alone
+const GC_GRACE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug)]
+pub enum ImageError {
+    Fetch(String),
+    Status(StatusCode),
+    TooLarge(usize),
+    NotAnImage,
+    Format,
+    Dimensions(u32, u32),
+    Fit(AdFormat, u32, u32),
+    Storage,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fetch(e) => write!(f, "couldn't download the image: {e}"),
+            Self::Status(status) => write!(f, "image host responded with {status}"),
+            Self::TooLarge(max) => write!(f, "image is larger than {} KiB", max / 1024),
+            Self::NotAnImage => write!(f, "that isn't an image"),
+            Self::Format => write!(f, "only png, jpeg, gif and webp images are allowed"),
+            Self::Dimensions(width, height) => {
+                write!(f, "image can be at most {width}x{height} pixels")
+            }
+            Self::Fit(format, width, height) => {
+                write!(f, "{}, this one is {width}x{height}", format.requirement())
+            }
+            Self::Storage => write!(f, "couldn't store the image"),
+        }
+    }
+}
+
+pub struct StoredImage {
+    pub hash: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+fn mime_of(kind: ImageType) -> Option<&'static str> {
+    match kind {
+        ImageType::Png => Some("image/png"),
+        ImageType::Jpeg => Some("image/jpeg"),
+        ImageType::Gif => Some("image/gif"),
+        ImageType::Webp => Some("image/webp"),
+        _ => None,
+    }
+}
+
+// Goes by the bytes, whatever the host claimed
+pub fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    imagesize::image_type(bytes).ok().and_then(mime_of)
+}
+
+pub fn valid_hash(hash: &str) -> bool {
+    hash.len() == 64
+        && hash
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+pub fn path_of(state: &AppState, hash: &str) -> PathBuf {
+    state.ad_images.dir.join(hash)
+}
+
+// Relative when the community has no public url set, which only the
+// default community is allowed
+pub fn local_url(state: &AppState, hash: &str) -> String {
+    format!(
+        "{}/ads/img/{hash}",
+        state.community.public_url.trim_end_matches('/')
+    )
+}
+
+pub async fn fetch(state: &AppState, url: &Url) -> Result<Vec<u8>, ImageError> {
+    let max_bytes = state.ad_images.max_bytes;
+
+    let mut response = state
+        .http
+        .get(url.clone())
+        .timeout(Duration::from_secs(state.ad_images.fetch_timeout_secs))
+        .send()
+        .await
+        .map_err(|e| ImageError::Fetch(e.to_string()))?;
+
+    if !response.status().is_success() {
+        return Err(ImageError::Status(response.status()));
+    }
+
+    if response
+        .content_length()
+        .is_some_and(|length| length as usize > max_bytes)
+    {
+        return Err(ImageError::TooLarge(max_bytes));
+    }
+
+    // Some hosts send no content type at all, the bytes get checked anyway
+    if let Some(content_type) = response.headers().get(CONTENT_TYPE)
+        && !content_type
+            .to_str()
+            .is_ok_and(|content_type| content_type.starts_with("image/"))
+    {
+        return Err(ImageError::NotAnImage);
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| ImageError::Fetch(e.to_string()))?
+    {
+        body.extend_from_slice(&chunk);
+        if body.len() > max_bytes {
+            return Err(ImageError::TooLarge(max_bytes));
+        }
+    }
+
+    Ok(body)
+}
+
+// Checked against `format` before anything is written, so images that don't
+// fit never reach the disk
+pub async fn store(
+    state: &AppState,
+    bytes: &[u8],
+    format: AdFormat,
+) -> Result<StoredImage, ImageError> {
+    let config = &state.ad_images;
+
+    if bytes.len() > config.max_bytes {
+        return Err(ImageError::TooLarge(config.max_bytes));
+    }
+
+    match imagesize::image_type(bytes) {
+        Ok(kind) if mime_of(kind).is_some() => {}
+        Ok(_) => return Err(ImageError::Format),
+        Err(_) => return Err(ImageError::NotAnImage),
+    }
+
+    let size = imagesize::blob_size(bytes).map_err(|_| ImageError::NotAnImage)?;
+    let (width, height) = (size.width as u32, size.height as u32);
+    if width == 0 || height == 0 || width > config.max_width || height > config.max_height {
+        return Err(ImageError::Dimensions(config.max_width, config.max_height));
+    }
+
+    if !format.fits(width, height) {
+        return Err(ImageError::Fit(format, width, height));
+    }
+
+    let hash = hex::encode(Sha256::digest(bytes));
+    let path = path_of(state, &hash);
+
+    // An image that's already stored is reused, but its mtime is bumped so
+    // the gc doesn't count it as old and unused before the ad row lands. If
+    // that fails (say gc got to it first) it's simply written again
+    let reused = tokio::fs::try_exists(&path).await.unwrap_or(false) && touch(&path).await.is_ok();
+
+    if !reused {
+        // Written next to the final file and renamed, so a half written image
+        // is never served
+        let partial = config
+            .dir
+            .join(format!("{hash}.{}.partial", uuid::Uuid::new_v4()));
+        let written = async {
+            tokio::fs::create_dir_all(&config.dir).await?;
+            tokio::fs::write(&partial, bytes).await?;
+            tokio::fs::rename(&partial, &path).await
+        }
+        .await;
+
+        if let Err(e) = written {
+            error!("Failed to store ad image {hash}: {e}");
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(ImageError::Storage);
+        }
+    }
+
+    Ok(StoredImage {
+        hash,
+        width,
+        height,
+    })
+}
+
+async fn touch(path: &Path) -> std::io::Result<()> {
+    let path = path.to_owned();
+    tokio::task::spawn_blocking(move || {
+        std::fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(SystemTime::now())
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+pub async fn ingest(
+    state: &AppState,
+    url: &Url,
+    format: AdFormat,
+) -> Result<StoredImage, ImageError> {
+    let bytes = fetch(state, url).await?;
+    store(state, &bytes, format).await
+}
+
+/* Backfill
+ *
+ * Ads from before images were stored still hotlink theirs, and their size
+ * was never measured, so the format they got when formats were added is a
+ * guess. They're downloaded once at startup and get the format their image
+ * actually fits. Approved ones that fit none go back to moderation. Ones
+ * that can't be fetched keep hotlinking and are tried again next start.
+ */
+pub fn spawn_backfill(state: AppState) {
+    tokio::spawn(async move {
+        match backfill(&state).await {
+            Ok(0) => {}
+            Ok(stored) => info!("Stored images for {stored} hotlinked ads"),
+            Err(e) => error!("Failed to backfill ad images: {e}"),
+        }
+    });
+}
+
+// The ad's own format first, so a guess that was right stays
+fn fitting_format(current: AdFormat, width: u32, height: u32) -> Option<AdFormat> {
+    if current.fits(width, height) {
+        return Some(current);
+    }
+
+    AdFormat::iter().find(|format| format.fits(width, height))
+}
+
+async fn backfill(state: &AppState) -> Result<usize, DbErr> {
+    let hotlinked = Ads::find()
+        .filter(ads::Column::ImageHash.eq(""))
+        .all(&state.db)
+        .await?;
+
+    if hotlinked.is_empty() {
+        return Ok(0);
+    }
+
+    let policy = url_policy::load(state).await?;
+    let audit = Audit::system("ad image backfill");
+    let mut stored = 0;
+
+    for ad in hotlinked {
+        let bytes = match policy.check(&ad.image_url, UrlKind::Image).await {
+            Ok(url) => fetch(state, &url).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        let size = bytes.and_then(|bytes| match imagesize::blob_size(&bytes) {
+            Ok(size) => Ok((bytes, size.width as u32, size.height as u32)),
+            Err(_) => Err(ImageError::NotAnImage.to_string()),
+        });
+
+        let (bytes, width, height) = match size {
+            Ok(size) => size,
+            Err(e) => {
+                warn!("Couldn't store the image of ad {}: {e}", ad.id);
+                continue;
+            }
+        };
+
+        let updated = match fitting_format(ad.format, width, height) {
+            Some(format) => match store(state, &bytes, format).await {
+                Ok(image) => {
+                    let mut active_ad: AdModel = ad.clone().into();
+                    active_ad.format = Set(format);
+                    active_ad.image_hash = Set(image.hash);
+                    active_ad.image_width = Set(image.width as i32);
+                    active_ad.image_height = Set(image.height as i32);
+                    active_ad.update(&state.db).await
+                }
+                Err(e) => {
+                    warn!("Couldn't store the image of ad {}: {e}", ad.id);
+                    continue;
+                }
+            },
+            None if ad.status == Status::Approved => {
+                let mut active_ad: AdModel = ad.clone().into();
+                active_ad.image_width = Set(width as i32);
+                active_ad.image_height = Set(height as i32);
+                active_ad.status = Set(Status::Pending);
+                active_ad.status_reason =
+                    Set("Image doesn't fit any ad format, send a new one".to_string());
+                active_ad.verified_at = Set(String::new());
+                active_ad.moderated_by = Set(None);
+                active_ad.moderated_at = Set(Utc::now().to_rfc3339());
+                active_ad.update(&state.db).await
+            }
+            None => continue,
+        };
+
+        // Another ad of the same member can already have the format
+        match updated {
+            Ok(updated) => {
+                audit
+                    .record(
+                        state,
+                        [ad.discord_id as u64],
+                        audit::snapshot(&AdResponse::from(&ad)),
+                        audit::snapshot(&AdResponse::from(&updated)),
+                    )
+                    .await;
+                if !updated.image_hash.is_empty() {
+                    stored += 1;
+                }
+            }
+            Err(e) => warn!("Failed to update ad {} after measuring it: {e}", ad.id),
+        }
+    }
+
+    Ok(stored)
+}
+
+// Every community's states, they all share `dir`
+pub fn spawn_gc(states: Vec<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(GC_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            match gc(&states).await {
+                Ok(0) => {}
+                Ok(removed) => info!("Removed {removed} unused ad images"),
+                Err(e) => error!("Failed to clean up ad images: {e}"),
+            }
+        }
+    });
+}
+
+async fn gc(states: &[AppState]) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(first) = states.first() else {
+        return Ok(0);
+    };
+    let dir = &first.ad_images.dir;
+
+    let mut used = HashSet::new();
+    for state in states {
+        let hashes: Vec<String> = Ads::find()
+            .select_only()
+            .column(ads::Column::ImageHash)
+            .filter(ads::Column::ImageHash.ne(""))
+            .into_tuple()
+            .all(&state.db)
+            .await?;
+        used.extend(hashes);
+    }
+
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut removed = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Leftover `.partial` files go too, anything else isn't ours
+        let hash = name.split('.').next().unwrap_or_default();
+        if !valid_hash(hash) || (name == hash && used.contains(hash)) {
+            continue;
+        }
+
+        let age = entry
+            .metadata()
+            .await?
+            .modified()?
+            .elapsed()
+            .unwrap_or_default();
+        if age < GC_GRACE {
+            continue;
+        }
+
+        match tokio::fs::remove_file(entry.path()).await {
+            Ok(()) => removed += 1,
+            Err(e) => warn!("Failed to remove ad image {name}: {e}"),
+        }
+    }
+
+    Ok(removed)
+}
@@ -0,0 +1,191 @@
+use super::database::{
+    ad_daily_stats, ads,
+    entities::{AdDailyStatModel, AdDailyStats, Ads},
+};
+use axum::http::{HeaderMap, header::USER_AGENT};
+use chrono::{NaiveDate, Utc};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QuerySelect, Set,
+    sea_query::{Expr, OnConflict},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{BuildHasher, RandomState},
+    net::SocketAddr,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/* Ad stats
+ *
+ * Impressions and clicks per ad per (UTC) day, kept in memory and written
+ * out together with the rotation counts. Someone seeing or clicking the same
+ * ad again within `DUPLICATE_WINDOW` only counts once. Viewers are told apart
+ * by a hash of their address and user agent, salted per process and never
+ * stored.
+ */
+
+const DUPLICATE_WINDOW: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Event {
+    Impression,
+    Click,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Counts {
+    impressions: i64,
+    clicks: i64,
+}
+
+#[derive(Default)]
+struct Buffer {
+    counts: HashMap<(i32, NaiveDate), Counts>,
+    seen: HashMap<(u64, i32, Event), Instant>,
+}
+
+pub struct AdStats {
+    salt: RandomState,
+    behind_proxy: bool,
+    buffer: Mutex<Buffer>,
+}
+
+impl AdStats {
+    pub fn new(behind_proxy: bool) -> Self {
+        Self {
+            salt: RandomState::new(),
+            behind_proxy,
+            buffer: Mutex::default(),
+        }
+    }
+
+    fn buffer(&self) -> MutexGuard<'_, Buffer> {
+        self.buffer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Behind a proxy the address is the proxy's, so the forwarded one wins.
+    // Anyone can send the header though, so only then.
+    pub fn viewer(&self, headers: &HeaderMap, address: SocketAddr) -> u64 {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .filter(|_| self.behind_proxy)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .map(|value| value.trim().to_string());
+        let address = forwarded.unwrap_or_else(|| address.ip().to_string());
+        let user_agent = headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok());
+
+        self.salt.hash_one((address, user_agent))
+    }
+
+    // Returns whether it was counted
+    pub fn record(&self, ad_id: i32, viewer: u64, event: Event) -> bool {
+        let mut buffer = self.buffer();
+        let now = Instant::now();
+
+        if buffer
+            .seen
+            .get(&(viewer, ad_id, event))
+            .is_some_and(|last| now.duration_since(*last) < DUPLICATE_WINDOW)
+        {
+            return false;
+        }
+        buffer.seen.insert((viewer, ad_id, event), now);
+
+        let counts = buffer
+            .counts
+            .entry((ad_id, Utc::now().date_naive()))
+            .or_default();
+        match event {
+            Event::Impression => counts.impressions += 1,
+            Event::Click => counts.clicks += 1,
+        }
+
+        true
+    }
+
+    pub async fn flush(&self, db: &DatabaseConnection) -> Result<usize, DbErr> {
+        let mut counts = {
+            let mut buffer = self.buffer();
+            let now = Instant::now();
+            buffer
+                .seen
+                .retain(|_, last| now.duration_since(*last) < DUPLICATE_WINDOW);
+            std::mem::take(&mut buffer.counts)
+        };
+        if counts.is_empty() {
+            return Ok(0);
+        }
+
+        // Ads deleted since have nothing left to count towards
+        let ids: HashSet<i32> = counts.keys().map(|(ad_id, _)| *ad_id).collect();
+        let existing: HashSet<i32> = match Ads::find()
+            .select_only()
+            .column(ads::Column::Id)
+            .filter(ads::Column::Id.is_in(ids))
+            .into_tuple::<i32>()
+            .all(db)
+            .await
+        {
+            Ok(existing) => existing.into_iter().collect(),
+            Err(e) => {
+                self.restore(counts);
+                return Err(e);
+            }
+        };
+        counts.retain(|(ad_id, _), _| existing.contains(ad_id));
+
+        let mut remaining = counts.clone();
+
+        for (key @ (ad_id, day), count) in &counts {
+            let row = AdDailyStatModel {
+                ad_id: Set(*ad_id),
+                day: Set(day.format("%Y-%m-%d").to_string()),
+                impressions: Set(count.impressions),
+                clicks: Set(count.clicks),
+                ..Default::default()
+            };
+
+            let result = AdDailyStats::insert(row)
+                .on_conflict(
+                    OnConflict::columns([
+                        ad_daily_stats::Column::AdId,
+                        ad_daily_stats::Column::Day,
+                    ])
+                    .value(
+                        ad_daily_stats::Column::Impressions,
+                        Expr::col(ad_daily_stats::Column::Impressions).add(count.impressions),
+                    )
+                    .value(
+                        ad_daily_stats::Column::Clicks,
+                        Expr::col(ad_daily_stats::Column::Clicks).add(count.clicks),
+                    )
+                    .to_owned(),
+                )
+                .exec(db)
+                .await;
+
+            if let Err(e) = result {
+                // Keep what wasn't written for the next flush
+                self.restore(remaining);
+                return Err(e);
+            }
+
+            remaining.remove(key);
+        }
+
+        Ok(counts.len())
+    }
+
+    fn restore(&self, counts: HashMap<(i32, NaiveDate), Counts>) {
+        let mut buffer = self.buffer();
+        for (key, count) in counts {
+            let pending = buffer.counts.entry(key).or_default();
+            pending.impressions += count.impressions;
+            pending.clicks += count.clicks;
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use super::{PetRingResult, database, jwt, state};
+use super::{
+    PetRingResult, audit, backlinks, database, directory, jwt, ring, search, state, url_policy,
+};
 
 use axum::{
     Json,
@@ -18,7 +20,8 @@ struct ServerInfo {
     name: String,
     version: String,
     description: String,
-    authors: [String; 2],
+    authors: Vec<String>,
+    url: String,
     license: String,
     source: String,
     server_uptime: String,
@@ -27,9 +30,13 @@ struct ServerInfo {
 
 #[derive(Serialize)]
 struct PublicAdResponse {
+    pub id: i32,
     pub username: String,
+    pub format: database::AdFormat,
     pub image_url: String,
     pub ad_url: String,
+    // Counts the click, then redirects to `ad_url`
+    pub click_url: String,
 }
 
 #[derive(Serialize)]
@@ -37,10 +44,91 @@ struct UserResponse {
     username: String,
     discord_id: u64,
     url: String,
+    // Kept for older clients, same as `status == "approved"`
     verified: bool,
+    status: database::Status,
+    status_reason: String,
     created_at: String,
     edited_at: String,
     verified_at: String,
+    #[serde(flatten)]
+    profile: ProfileResponse,
+}
+
+impl From<&database::users::Model> for UserResponse {
+    fn from(user: &database::users::Model) -> Self {
+        Self {
+            username: user.username.clone(),
+            discord_id: user.discord_id as u64,
+            url: user.url.clone(),
+            verified: user.status == database::Status::Approved,
+            status: user.status,
+            status_reason: user.status_reason.clone(),
+            created_at: user.created_at.clone(),
+            edited_at: user.edited_at.clone(),
+            verified_at: user.verified_at.clone(),
+            profile: ProfileResponse::from(user),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ProfileResponse {
+    pub description: String,
+    pub avatar_url: String,
+    pub button_url: String,
+    pub pronouns: String,
+    pub language: String,
+    pub tags: Vec<String>,
+}
+
+impl From<&database::users::Model> for ProfileResponse {
+    fn from(user: &database::users::Model) -> Self {
+        Self {
+            description: user.description.clone(),
+            avatar_url: user.avatar_url.clone(),
+            button_url: user.button_url.clone(),
+            pronouns: user.pronouns.clone(),
+            language: user.language.clone(),
+            tags: user
+                .tags
+                .split(',')
+                .filter(|tag| !tag.is_empty())
+                .map(|tag| tag.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BacklinkResponse {
+    verified: bool,
+    markers: Vec<String>,
+    error: String,
+    checked_at: String,
+}
+
+impl From<&database::users::Model> for BacklinkResponse {
+    fn from(user: &database::users::Model) -> Self {
+        Self {
+            verified: user.backlink_verified,
+            markers: user
+                .backlink_markers
+                .split(',')
+                .filter(|marker| !marker.is_empty())
+                .map(|marker| marker.to_string())
+                .collect(),
+            error: user.backlink_error.clone(),
+            checked_at: user.backlink_checked_at.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct UnverifiedUserResponse {
+    #[serde(flatten)]
+    user: UserResponse,
+    backlinks: BacklinkResponse,
 }
 
 #[derive(Serialize)]
@@ -55,32 +143,191 @@ pub struct Serializeableuser {
     pub url: String,
 }
 
+#[derive(Serialize)]
+pub struct NeighborsResponse {
+    pub username: String,
+    pub prev: Option<Serializeableuser>,
+    pub next: Option<Serializeableuser>,
+    pub random: Option<Serializeableuser>,
+}
+
+#[derive(Serialize)]
+pub struct DirectoryUser {
+    pub username: String,
+    pub url: String,
+    #[serde(flatten)]
+    pub profile: ProfileResponse,
+}
+
 #[derive(Serialize)]
 pub struct UsersResponse {
-    pub users: Vec<Serializeableuser>,
+    pub users: Vec<DirectoryUser>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResultResponse {
+    #[serde(flatten)]
+    pub user: DirectoryUser,
+    pub snippet: String,
+    pub rank: f64,
+}
+
+#[derive(Serialize)]
+pub struct SearchResponse {
+    pub query: String,
+    pub users: Vec<SearchResultResponse>,
+}
+
+#[derive(Serialize)]
+struct UserListResponse {
+    users: Vec<UserResponse>,
+    next_cursor: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct AdSubmission {
     pub image_url: String,
     pub discord_id: u64,
+    #[serde(default)]
+    pub format: database::AdFormat,
+}
+
+// Members can have an ad per format. Leaving the format out works as long
+// as they only have the one.
+#[derive(Deserialize)]
+pub struct AdFormatQuery {
+    pub format: Option<database::AdFormat>,
 }
 
 #[derive(Serialize)]
 pub struct AdResponse {
     pub username: String,
     pub discord_id: u64,
+    pub format: database::AdFormat,
     pub image_url: String,
+    // Empty until the image has been downloaded
+    pub image_hash: String,
+    pub image_width: u32,
+    pub image_height: u32,
     pub ad_url: String,
+    pub weight: i32,
+    // Lags behind by up to one flush interval
+    pub impressions: i64,
+    pub starts_at: String,
+    pub ends_at: String,
     pub verified: bool,
+    pub status: database::Status,
+    pub status_reason: String,
     pub created_at: String,
     pub edited_at: String,
     pub verified_at: String,
 }
 
+impl From<&database::ads::Model> for AdResponse {
+    fn from(ad: &database::ads::Model) -> Self {
+        Self {
+            username: ad.username.clone(),
+            discord_id: ad.discord_id as u64,
+            format: ad.format,
+            image_url: ad.image_url.clone(),
+            image_hash: ad.image_hash.clone(),
+            image_width: ad.image_width as u32,
+            image_height: ad.image_height as u32,
+            ad_url: ad.ad_url.clone(),
+            weight: ad.weight,
+            impressions: ad.impressions,
+            starts_at: ad.starts_at.clone(),
+            ends_at: ad.ends_at.clone(),
+            verified: ad.status == database::Status::Approved,
+            status: ad.status,
+            status_reason: ad.status_reason.clone(),
+            created_at: ad.created_at.clone(),
+            edited_at: ad.edited_at.clone(),
+            verified_at: ad.verified_at.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AdWeightRequest {
+    pub discord_id: u64,
+    pub format: Option<database::AdFormat>,
+    pub weight: i32,
+}
+
+// Leaving a date out keeps it, an empty one removes it
+#[derive(Deserialize)]
+pub struct AdScheduleRequest {
+    pub discord_id: u64,
+    pub format: Option<database::AdFormat>,
+    pub starts_at: Option<String>,
+    pub ends_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ExpiringAdsQuery {
+    pub days: Option<u32>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdExpiry {
+    // Goes back to pending and needs approving again
+    Approval,
+    // Reaches its `ends_at`
+    Campaign,
+}
+
+#[derive(Serialize)]
+pub struct ExpiringAdResponse {
+    pub expires_at: String,
+    pub reason: AdExpiry,
+    pub ad: AdResponse,
+}
+
+#[derive(Serialize)]
+pub struct ExpiringAdsResponse {
+    pub days: u32,
+    // Soonest first
+    pub ads: Vec<ExpiringAdResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct AdStatsQuery {
+    pub days: Option<u32>,
+    pub format: Option<database::AdFormat>,
+}
+
+#[derive(Serialize)]
+pub struct AdStatsResponse {
+    pub discord_id: u64,
+    pub format: database::AdFormat,
+    pub days: u32,
+    pub impressions: i64,
+    pub clicks: i64,
+    // Oldest first, days without any traffic are included as zeroes.
+    // Lags behind by up to one flush interval.
+    pub daily: Vec<AdDailyStatResponse>,
+}
+
+#[derive(Serialize)]
+pub struct AdDailyStatResponse {
+    pub day: String,
+    pub impressions: i64,
+    pub clicks: i64,
+}
+
 #[derive(Deserialize)]
 pub struct AdEditRequest {
     pub discord_id: u64,
+    pub format: Option<database::AdFormat>,
     pub url: Option<String>,
 }
 
@@ -107,11 +354,24 @@ pub struct BulkAdDeleteResponse {
     pub image_urls: Vec<String>,
 }
 
+// Optional profile fields, shared by submissions and edits
+#[derive(Deserialize)]
+pub struct ProfileFields {
+    pub description: Option<String>,
+    pub avatar_url: Option<String>,
+    pub button_url: Option<String>,
+    pub pronouns: Option<String>,
+    pub language: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
 #[derive(Deserialize)]
 pub struct UserSubmission {
     pub username: String,
     pub url: String,
     pub discord_id: u64,
+    #[serde(flatten)]
+    pub profile: ProfileFields,
 }
 
 #[derive(Deserialize)]
@@ -119,6 +379,299 @@ pub struct UserEdit {
     pub discord_id: u64,
     pub username: Option<String>,
     pub url: Option<String>,
+    #[serde(flatten)]
+    pub profile: ProfileFields,
+}
+
+#[derive(Deserialize)]
+pub struct ModerationRequest {
+    pub discord_id: u64,
+    pub reason: String,
+    // Discord ID of the moderator acting on it
+    pub moderator_id: u64,
+    // Only used for ads, see `AdFormatQuery`
+    pub format: Option<database::AdFormat>,
+}
+
+#[derive(Serialize)]
+struct QueueResponse {
+    users: Vec<UserResponse>,
+    ads: Vec<AdResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct UserVisibilityRequest {
+    pub discord_id: u64,
+    pub hidden: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct UserVisibilityResponse {
+    pub username: String,
+    pub discord_id: u64,
+    pub hidden: bool,
+}
+
+#[derive(Deserialize)]
+pub struct RingMoveRequest {
+    pub discord_id: u64,
+    pub position: usize,
+}
+
+#[derive(Deserialize)]
+pub struct RingSwapRequest {
+    pub first_discord_id: u64,
+    pub second_discord_id: u64,
+}
+
+#[derive(Deserialize)]
+pub struct RingOrderRequest {
+    pub discord_ids: Vec<u64>,
+}
+
+#[derive(Serialize)]
+pub struct RingOrderEntry {
+    pub position: i32,
+    pub username: String,
+    pub discord_id: u64,
+}
+
+#[derive(Serialize)]
+pub struct RingOrderResponse {
+    pub users: Vec<RingOrderEntry>,
+}
+
+#[derive(Serialize)]
+pub struct RingSummary {
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    pub member_count: u64,
+}
+
+#[derive(Serialize)]
+pub struct RingsResponse {
+    pub rings: Vec<RingSummary>,
+}
+
+#[derive(Serialize)]
+pub struct ButtonResponse {
+    pub username: String,
+    pub url: String,
+    pub image_url: String,
+}
+
+#[derive(Serialize)]
+pub struct ButtonsResponse {
+    pub ring: String,
+    // In ring order
+    pub buttons: Vec<ButtonResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct RingCreateRequest {
+    pub slug: String,
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RingMembersRequest {
+    pub discord_ids: Vec<u64>,
+}
+
+#[derive(Serialize)]
+pub struct RingResponse {
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    pub created_at: String,
+    pub members: Vec<RingOrderEntry>,
+}
+
+#[derive(Deserialize)]
+pub struct LinkHealthQuery {
+    pub down: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct LinkHealthResponse {
+    pub username: String,
+    pub discord_id: u64,
+    pub url: String,
+    pub status_code: Option<i32>,
+    pub latency_ms: Option<i64>,
+    pub error: String,
+    pub last_checked_at: String,
+    pub last_up_at: String,
+    pub down_since: String,
+    pub offline: bool,
+}
+
+#[derive(Serialize)]
+pub struct LinkHealthListResponse {
+    pub members: Vec<LinkHealthResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub target: Option<u64>,
+    pub actor: Option<u64>,
+    // Id of the last entry on the previous page
+    pub cursor: Option<i32>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct AuditEntryResponse {
+    pub id: i32,
+    pub actor_id: Option<u64>,
+    pub endpoint: String,
+    pub targets: Vec<u64>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub created_at: String,
+}
+
+impl From<database::audit_log::Model> for AuditEntryResponse {
+    fn from(entry: database::audit_log::Model) -> Self {
+        Self {
+            id: entry.id,
+            actor_id: entry.actor_id.map(|id| id as u64),
+            endpoint: entry.endpoint,
+            targets: entry
+                .targets
+                .split(',')
+                .filter_map(|id| id.parse().ok())
+                .collect(),
+            before: entry.before,
+            after: entry.after,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct AuditResponse {
+    pub entries: Vec<AuditEntryResponse>,
+    pub next_cursor: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct BanQuery {
+    pub kind: Option<database::BanKind>,
+    // Leave out to include expired bans
+    pub active: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct BanRequest {
+    pub kind: database::BanKind,
+    pub value: String,
+    pub reason: String,
+    pub expires_at: Option<String>,
+}
+
+// An empty `expires_at` makes the ban permanent
+#[derive(Deserialize)]
+pub struct BanEditRequest {
+    pub reason: Option<String>,
+    pub expires_at: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BanResponse {
+    pub id: i32,
+    pub kind: database::BanKind,
+    pub value: String,
+    pub reason: String,
+    pub expires_at: String,
+    pub created_by: Option<u64>,
+    pub created_at: String,
+}
+
+impl From<&database::bans::Model> for BanResponse {
+    fn from(ban: &database::bans::Model) -> Self {
+        Self {
+            id: ban.id,
+            kind: ban.kind,
+            value: ban.value.clone(),
+            reason: ban.reason.clone(),
+            expires_at: ban.expires_at.clone(),
+            created_by: ban.created_by.map(|id| id as u64),
+            created_at: ban.created_at.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BansResponse {
+    pub bans: Vec<BanResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct UrlRuleRequest {
+    pub kind: database::UrlKind,
+    pub action: database::RuleAction,
+    // A bare hostname or a whole url, only the host is kept
+    pub host: String,
+}
+
+#[derive(Serialize)]
+pub struct UrlRuleResponse {
+    // Rules from the config file have no id and can't be deleted
+    pub id: Option<i32>,
+    pub kind: database::UrlKind,
+    pub action: database::RuleAction,
+    pub host: String,
+    pub source: &'static str,
+}
+
+impl From<&url_policy::Rule> for UrlRuleResponse {
+    fn from(rule: &url_policy::Rule) -> Self {
+        Self {
+            id: rule.id,
+            kind: rule.kind,
+            action: rule.action,
+            host: rule.host.clone(),
+            source: if rule.id.is_some() {
+                "database"
+            } else {
+                "config"
+            },
+        }
+    }
+}
+
+impl From<&database::url_rules::Model> for UrlRuleResponse {
+    fn from(rule: &database::url_rules::Model) -> Self {
+        Self {
+            id: Some(rule.id),
+            kind: rule.kind,
+            action: rule.action,
+            host: rule.host.clone(),
+            source: "database",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct UrlPolicyResponse {
+    pub resolve_hosts: bool,
+    pub rules: Vec<UrlRuleResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct UrlCheckQuery {
+    pub url: String,
+    pub kind: database::UrlKind,
+}
+
+#[derive(Serialize)]
+pub struct UrlCheckResponse {
+    pub url: String,
+    pub allowed: bool,
+    pub reason: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -127,6 +680,22 @@ pub(crate) struct PetRingApiResponse {
     pub message: String,
 }
 
+pub(crate) fn list_error_response(error: directory::ListError) -> Response<Body> {
+    match error {
+        directory::ListError::InvalidCursor => {
+            petring_api_err(StatusCode::BAD_REQUEST, "Invalid cursor")
+        }
+        directory::ListError::InvalidLimit => {
+            petring_api_err(StatusCode::BAD_REQUEST, "Invalid limit")
+        }
+        directory::ListError::InvalidTag => petring_api_err(StatusCode::BAD_REQUEST, "Invalid tag"),
+        directory::ListError::Db(e) => {
+            error!("Failed to list users: {e}");
+            petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users")
+        }
+    }
+}
+
 pub(crate) fn petring_api_err(status: StatusCode, message: &str) -> Response<Body> {
     (
         status,
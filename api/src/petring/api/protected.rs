@@ -1,10 +1,25 @@
+pub mod audit_log;
+pub mod bans;
+pub mod health;
+pub mod moderation;
 pub mod petads;
 pub mod petring;
+pub mod rings;
+pub mod url_rules;
 
 use super::{
-    AdEditRequest, AdResponse, AdSubmission, BulkAdDeleteRequest, BulkAdDeleteResponse,
-    BulkUserDeleteRequest, BulkUserDeleteResponse, EditUserResponse, UserEdit, UserResponse,
-    UserSubmission, database, jwt, petring_api_err, petring_api_response,
+    AdDailyStatResponse, AdEditRequest, AdExpiry, AdFormatQuery, AdResponse, AdScheduleRequest,
+    AdStatsQuery, AdStatsResponse, AdSubmission, AdWeightRequest, AuditEntryResponse, AuditQuery,
+    AuditResponse, BacklinkResponse, BanEditRequest, BanQuery, BanRequest, BanResponse,
+    BansResponse, BulkAdDeleteRequest, BulkAdDeleteResponse, BulkUserDeleteRequest,
+    BulkUserDeleteResponse, EditUserResponse, ExpiringAdResponse, ExpiringAdsQuery,
+    ExpiringAdsResponse, LinkHealthListResponse, LinkHealthQuery, LinkHealthResponse,
+    ModerationRequest, ProfileFields, QueueResponse, RingCreateRequest, RingMembersRequest,
+    RingMoveRequest, RingOrderEntry, RingOrderRequest, RingOrderResponse, RingResponse,
+    RingSwapRequest, UnverifiedUserResponse, UrlCheckQuery, UrlCheckResponse, UrlPolicyResponse,
+    UrlRuleRequest, UrlRuleResponse, UserEdit, UserListResponse, UserResponse, UserSubmission,
+    UserVisibilityRequest, UserVisibilityResponse, audit, backlinks, database, directory, jwt,
+    list_error_response, petring_api_err, petring_api_response, ring,
     state::{self, AppState},
 };
 
@@ -156,12 +171,19 @@ pub async fn require_auth(
     };
 
     let json_content_type = "application/json";
+    // Only used for uploads, the boundary makes it a prefix match
+    let multipart_content_type = "multipart/form-data";
 
     let token = authorization.to_str().unwrap().split_at(7).1;
     let token_secrets = state.token_secrets.lock().await;
     match jwt::verify_token(token, &token_secrets) {
-        Ok(_) => {
-            if request.method() != Method::GET && content_type != json_content_type {
+        // Tokens are signed with the same secrets for every community, so
+        // make sure this one was issued to this community's bot.
+        Ok(claims) if claims.sub == state.bot_token => {
+            if request.method() != Method::GET
+                && content_type != json_content_type
+                && !content_type.starts_with(multipart_content_type)
+            {
                 return Err(petring_api_err(
                     StatusCode::BAD_REQUEST,
                     "Wrong content type",
@@ -169,7 +191,7 @@ pub async fn require_auth(
             }
             Ok(next.run(request).await)
         }
-        Err(_) => {
+        _ => {
             info!("Failed to verify token, invalid token: {token}");
             Err(petring_api_err(StatusCode::UNAUTHORIZED, "Invalid token"))
         }
@@ -0,0 +1,75 @@
+use super::{
+    AuditEntryResponse, AuditQuery, AuditResponse,
+    database::{audit_log, entities::AuditLog},
+    petring_api_err, petring_api_response,
+    state::AppState,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, sea_query::Expr};
+
+/* GET /get/audit
+ *
+ * Newest first, `target` and `actor` take Discord IDs.
+ */
+
+const DEFAULT_PAGE_SIZE: u64 = 50;
+const MAX_PAGE_SIZE: u64 = 200;
+
+pub async fn get_audit(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    let limit = match query.limit {
+        Some(0) => return petring_api_err(StatusCode::BAD_REQUEST, "Invalid limit"),
+        Some(limit) => limit.min(MAX_PAGE_SIZE),
+        None => DEFAULT_PAGE_SIZE,
+    };
+
+    let mut select = AuditLog::find().order_by_desc(audit_log::Column::Id);
+
+    if let Some(target) = query.target {
+        // Targets are stored comma separated, pad both ends to match whole ids
+        select = select.filter(Expr::cust_with_values(
+            r#"(',' || "audit_log"."targets" || ',') LIKE ?"#,
+            [format!("%,{target},%")],
+        ));
+    }
+
+    if let Some(actor) = query.actor {
+        select = select.filter(audit_log::Column::ActorId.eq(actor as i64));
+    }
+
+    if let Some(cursor) = query.cursor {
+        select = select.filter(audit_log::Column::Id.lt(cursor));
+    }
+
+    // Grab one extra entry to know whether there's another page
+    let mut entries = match select.limit(limit + 1).all(&state.db).await {
+        Ok(entries) => entries,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch audit log",
+            );
+        }
+    };
+
+    let next_cursor = if entries.len() as u64 > limit {
+        entries.truncate(limit as usize);
+        entries.last().map(|entry| entry.id)
+    } else {
+        None
+    };
+
+    petring_api_response(
+        StatusCode::OK,
+        AuditResponse {
+            entries: entries.into_iter().map(AuditEntryResponse::from).collect(),
+            next_cursor,
+        },
+    )
+}
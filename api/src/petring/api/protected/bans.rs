@@ -0,0 +1,220 @@
+use super::{
+    BanEditRequest, BanQuery, BanRequest, BanResponse, BansResponse,
+    audit::{self, Audit},
+    database::{
+        BanKind, bans,
+        entities::{BanModel, Bans},
+    },
+    petring_api_err, petring_api_response,
+    state::AppState,
+};
+use crate::petring::bans::{active, parse_host};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde_json::Value;
+
+/* Ban list
+ *
+ * Host bans take either a bare hostname or a whole url, only the host is
+ * kept. Expiry dates are RFC 3339 and stored in UTC so they compare as
+ * strings.
+ */
+
+const MAX_REASON_LENGTH: usize = 500;
+
+fn ban_value(kind: BanKind, value: &str) -> Option<String> {
+    match kind {
+        BanKind::DiscordId => value.trim().parse::<u64>().ok().map(|id| id.to_string()),
+        BanKind::Host => parse_host(value),
+    }
+}
+
+fn expiry(expires_at: &str) -> Result<String, &'static str> {
+    if expires_at.trim().is_empty() {
+        return Ok("".to_string());
+    }
+
+    let expires_at = DateTime::parse_from_rfc3339(expires_at.trim())
+        .map_err(|_| "Invalid expiry, expected an RFC 3339 date")?
+        .with_timezone(&Utc);
+
+    if expires_at <= Utc::now() {
+        return Err("Expiry has to be in the future");
+    }
+
+    Ok(expires_at.to_rfc3339())
+}
+
+// Discord ID bans show up in the audit log under that member
+fn target(ban: &bans::Model) -> Option<u64> {
+    match ban.kind {
+        BanKind::DiscordId => ban.value.parse().ok(),
+        BanKind::Host => None,
+    }
+}
+
+fn valid_reason(reason: &str) -> bool {
+    reason.chars().count() <= MAX_REASON_LENGTH && !reason.chars().any(char::is_control)
+}
+
+pub async fn get_bans(
+    State(state): State<AppState>,
+    Query(query): Query<BanQuery>,
+) -> impl IntoResponse {
+    let mut select = Bans::find().order_by_desc(bans::Column::Id);
+
+    if let Some(kind) = query.kind {
+        select = select.filter(bans::Column::Kind.eq(kind));
+    }
+
+    match query.active {
+        Some(true) => select = select.filter(active()),
+        Some(false) => select = select.filter(active().not()),
+        None => {}
+    }
+
+    match select.all(&state.db).await {
+        Ok(bans) => petring_api_response(
+            StatusCode::OK,
+            BansResponse {
+                bans: bans.iter().map(BanResponse::from).collect(),
+            },
+        ),
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch bans"),
+    }
+}
+
+pub async fn post_ban(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(request): Json<BanRequest>,
+) -> impl IntoResponse {
+    let value = match ban_value(request.kind, &request.value) {
+        Some(value) => value,
+        None => return petring_api_err(StatusCode::BAD_REQUEST, "Invalid value"),
+    };
+
+    let reason = request.reason.trim().to_string();
+    if !valid_reason(&reason) {
+        return petring_api_err(StatusCode::BAD_REQUEST, "Invalid reason");
+    }
+
+    let expires_at = match expiry(request.expires_at.as_deref().unwrap_or_default()) {
+        Ok(expires_at) => expires_at,
+        Err(message) => return petring_api_err(StatusCode::BAD_REQUEST, message),
+    };
+
+    match Bans::find()
+        .filter(bans::Column::Kind.eq(request.kind))
+        .filter(bans::Column::Value.eq(value.clone()))
+        .one(&state.db)
+        .await
+    {
+        Ok(Some(_)) => return petring_api_err(StatusCode::CONFLICT, "Ban already exists"),
+        Ok(None) => {}
+        Err(_) => return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ban"),
+    }
+
+    let ban = BanModel {
+        kind: Set(request.kind),
+        value: Set(value),
+        reason: Set(reason),
+        expires_at: Set(expires_at),
+        created_by: Set(audit.actor_id().map(|id| id as i64)),
+        created_at: Set(Utc::now().to_rfc3339()),
+        ..Default::default()
+    };
+
+    match ban.insert(&state.db).await {
+        Ok(ban) => {
+            let after = BanResponse::from(&ban);
+            audit
+                .record(&state, target(&ban), Value::Null, audit::snapshot(&after))
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(e) => petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to insert ban: {e}"),
+        ),
+    }
+}
+
+pub async fn patch_ban(
+    State(state): State<AppState>,
+    audit: Audit,
+    Path(id): Path<i32>,
+    Json(request): Json<BanEditRequest>,
+) -> impl IntoResponse {
+    let ban = match Bans::find_by_id(id).one(&state.db).await {
+        Ok(Some(ban)) => ban,
+        Ok(None) => return petring_api_err(StatusCode::NOT_FOUND, "Ban not found"),
+        Err(_) => return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ban"),
+    };
+
+    if request.reason.is_none() && request.expires_at.is_none() {
+        return petring_api_err(StatusCode::NOT_MODIFIED, "No changes made");
+    }
+
+    let mut active_ban: BanModel = ban.clone().into();
+
+    if let Some(reason) = request.reason {
+        let reason = reason.trim().to_string();
+        if !valid_reason(&reason) {
+            return petring_api_err(StatusCode::BAD_REQUEST, "Invalid reason");
+        }
+        active_ban.reason = Set(reason);
+    }
+
+    if let Some(expires_at) = request.expires_at {
+        match expiry(&expires_at) {
+            Ok(expires_at) => active_ban.expires_at = Set(expires_at),
+            Err(message) => return petring_api_err(StatusCode::BAD_REQUEST, message),
+        }
+    }
+
+    match active_ban.update(&state.db).await {
+        Ok(updated) => {
+            let after = BanResponse::from(&updated);
+            audit
+                .record(
+                    &state,
+                    target(&updated),
+                    audit::snapshot(&BanResponse::from(&ban)),
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update ban"),
+    }
+}
+
+pub async fn delete_ban(
+    State(state): State<AppState>,
+    audit: Audit,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let ban = match Bans::find_by_id(id).one(&state.db).await {
+        Ok(Some(ban)) => ban,
+        Ok(None) => return petring_api_err(StatusCode::NOT_FOUND, "Ban not found"),
+        Err(_) => return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ban"),
+    };
+
+    match Bans::delete_by_id(ban.id).exec(&state.db).await {
+        Ok(_) => {
+            let before = BanResponse::from(&ban);
+            audit
+                .record(&state, target(&ban), audit::snapshot(&before), Value::Null)
+                .await;
+            petring_api_response(StatusCode::OK, before)
+        }
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete ban"),
+    }
+}
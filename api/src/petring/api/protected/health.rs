@@ -0,0 +1,114 @@
+use super::{
+    LinkHealthListResponse, LinkHealthQuery, LinkHealthResponse,
+    database::{
+        entities::{LinkHealth, Users},
+        link_health, users,
+    },
+    petring_api_err, petring_api_response,
+    state::AppState,
+};
+use crate::petring::health::is_offline;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+
+fn link_health_response(
+    state: &AppState,
+    health: link_health::Model,
+    user: users::Model,
+) -> LinkHealthResponse {
+    LinkHealthResponse {
+        offline: is_offline(state, &health),
+        username: user.username,
+        discord_id: user.discord_id as u64,
+        url: user.url,
+        status_code: health.status_code,
+        latency_ms: health.latency_ms,
+        error: health.error,
+        last_checked_at: health.last_checked_at,
+        last_up_at: health.last_up_at,
+        down_since: health.down_since,
+    }
+}
+
+pub async fn get_link_health(
+    State(state): State<AppState>,
+    Query(query): Query<LinkHealthQuery>,
+) -> impl IntoResponse {
+    let mut select = LinkHealth::find()
+        .find_also_related(Users)
+        .order_by_asc(link_health::Column::DownSince);
+
+    if query.down.unwrap_or(false) {
+        select = select.filter(link_health::Column::DownSince.ne(""));
+    }
+
+    let records = match select.all(&state.db).await {
+        Ok(records) => records,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch link health",
+            );
+        }
+    };
+
+    petring_api_response(
+        StatusCode::OK,
+        LinkHealthListResponse {
+            members: records
+                .into_iter()
+                .filter_map(|(health, user)| {
+                    user.map(|user| link_health_response(&state, health, user))
+                })
+                .collect(),
+        },
+    )
+}
+
+pub async fn get_link_health_by_discord_id(
+    State(state): State<AppState>,
+    Path(discord_id): Path<u64>,
+) -> impl IntoResponse {
+    let user = match Users::find()
+        .filter(users::Column::DiscordId.eq(discord_id as i64))
+        .one(&state.db)
+        .await
+    {
+        Ok(user) => user,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+        }
+    };
+
+    let user = match user {
+        Some(user) => user,
+        None => {
+            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
+        }
+    };
+
+    let health = match LinkHealth::find()
+        .filter(link_health::Column::UserId.eq(user.id))
+        .one(&state.db)
+        .await
+    {
+        Ok(health) => health,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch link health",
+            );
+        }
+    };
+
+    match health {
+        Some(health) => {
+            petring_api_response(StatusCode::OK, link_health_response(&state, health, user))
+        }
+        None => petring_api_err(StatusCode::NOT_FOUND, "User hasn't been checked yet"),
+    }
+}
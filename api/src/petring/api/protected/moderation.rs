@@ -0,0 +1,249 @@
+use super::{
+    AdResponse, ModerationRequest, QueueResponse, UserResponse,
+    audit::{self, Audit},
+    database::{
+        Status, ads,
+        entities::{AdModel, Ads, UserModel, Users},
+        users,
+    },
+    petads, petring_api_err, petring_api_response,
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set,
+    sea_query::{Expr, SimpleExpr},
+};
+use tracing::info;
+
+/* Moderation
+ *
+ * Submissions start out pending and get approved through the verify
+ * endpoints. Pending ones can be rejected and approved ones suspended, both
+ * keep the row around with the reason so the member can be told why.
+ */
+
+const MAX_REASON_LENGTH: usize = 500;
+
+fn valid_reason(reason: &str) -> Result<String, &'static str> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err("A reason is required");
+    }
+
+    if reason.chars().count() > MAX_REASON_LENGTH || reason.chars().any(char::is_control) {
+        return Err("Invalid reason");
+    }
+
+    Ok(reason.to_string())
+}
+
+async fn moderate_user(
+    state: AppState,
+    audit: Audit,
+    request: ModerationRequest,
+    from: Status,
+    to: Status,
+) -> Response {
+    let reason = match valid_reason(&request.reason) {
+        Ok(reason) => reason,
+        Err(message) => return petring_api_err(StatusCode::BAD_REQUEST, message),
+    };
+
+    let user = match Users::find()
+        .filter(users::Column::DiscordId.eq(request.discord_id as i64))
+        .one(&state.db)
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return petring_api_err(StatusCode::NOT_FOUND, "User not found"),
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+        }
+    };
+
+    if user.status != from {
+        return petring_api_err(
+            StatusCode::CONFLICT,
+            match to {
+                Status::Suspended => "Only approved users can be suspended",
+                _ => "Only pending users can be rejected",
+            },
+        );
+    }
+
+    let before = audit::snapshot(&UserResponse::from(&user));
+    let mut active_user: UserModel = user.into();
+    active_user.status = Set(to);
+    active_user.status_reason = Set(reason);
+    active_user.moderated_by = Set(Some(request.moderator_id as i64));
+    active_user.moderated_at = Set(Utc::now().to_rfc3339());
+
+    match active_user.update(&state.db).await {
+        Ok(updated) => {
+            info!(
+                "User {} is now {:?} by {}",
+                updated.username, to, request.moderator_id
+            );
+            let after = UserResponse::from(&updated);
+            audit
+                .acting_as(request.moderator_id)
+                .record(
+                    &state,
+                    [request.discord_id],
+                    before,
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update user"),
+    }
+}
+
+async fn moderate_ad(
+    state: AppState,
+    audit: Audit,
+    request: ModerationRequest,
+    from: Status,
+    to: Status,
+) -> Response {
+    let reason = match valid_reason(&request.reason) {
+        Ok(reason) => reason,
+        Err(message) => return petring_api_err(StatusCode::BAD_REQUEST, message),
+    };
+
+    let ad = match petads::find_ad(
+        &state,
+        ads::Column::DiscordId.eq(request.discord_id as i64),
+        request.format,
+    )
+    .await
+    {
+        Ok(ad) => ad,
+        Err(response) => return response,
+    };
+
+    if ad.status != from {
+        return petring_api_err(
+            StatusCode::CONFLICT,
+            match to {
+                Status::Suspended => "Only approved ads can be suspended",
+                _ => "Only pending ads can be rejected",
+            },
+        );
+    }
+
+    let before = audit::snapshot(&AdResponse::from(&ad));
+    let mut active_ad: AdModel = ad.into();
+    active_ad.status = Set(to);
+    active_ad.status_reason = Set(reason);
+    active_ad.moderated_by = Set(Some(request.moderator_id as i64));
+    active_ad.moderated_at = Set(Utc::now().to_rfc3339());
+
+    match active_ad.update(&state.db).await {
+        Ok(updated) => {
+            info!(
+                "Ad for {} is now {:?} by {}",
+                updated.username, to, request.moderator_id
+            );
+            let after = AdResponse::from(&updated);
+            audit
+                .acting_as(request.moderator_id)
+                .record(
+                    &state,
+                    [request.discord_id],
+                    before,
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update ad"),
+    }
+}
+
+pub async fn patch_user_reject(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(request): Json<ModerationRequest>,
+) -> impl IntoResponse {
+    moderate_user(state, audit, request, Status::Pending, Status::Rejected).await
+}
+
+pub async fn patch_user_suspend(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(request): Json<ModerationRequest>,
+) -> impl IntoResponse {
+    moderate_user(state, audit, request, Status::Approved, Status::Suspended).await
+}
+
+pub async fn patch_ad_reject(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(request): Json<ModerationRequest>,
+) -> impl IntoResponse {
+    moderate_ad(state, audit, request, Status::Pending, Status::Rejected).await
+}
+
+pub async fn patch_ad_suspend(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(request): Json<ModerationRequest>,
+) -> impl IntoResponse {
+    moderate_ad(state, audit, request, Status::Approved, Status::Suspended).await
+}
+
+/* GET /get/queue
+ *
+ * Everything waiting on a moderator, oldest first.
+ */
+
+// When a row last went (back) to pending. `moderated_at` is set whenever
+// something puts it back in the queue and stays empty for fresh submissions.
+fn entered_pending() -> SimpleExpr {
+    Expr::cust(r#"CASE WHEN "moderated_at" = '' THEN "created_at" ELSE "moderated_at" END"#)
+}
+
+pub async fn get_queue(State(state): State<AppState>) -> impl IntoResponse {
+    let users = match Users::find()
+        .filter(users::Column::Status.eq(Status::Pending))
+        .order_by_asc(entered_pending())
+        .order_by_asc(users::Column::Id)
+        .all(&state.db)
+        .await
+    {
+        Ok(users) => users,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users");
+        }
+    };
+
+    let ads = match Ads::find()
+        .filter(ads::Column::Status.eq(Status::Pending))
+        .order_by_asc(entered_pending())
+        .order_by_asc(ads::Column::Id)
+        .all(&state.db)
+        .await
+    {
+        Ok(ads) => ads,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ads");
+        }
+    };
+
+    petring_api_response(
+        StatusCode::OK,
+        QueueResponse {
+            users: users.iter().map(UserResponse::from).collect(),
+            ads: ads.iter().map(AdResponse::from).collect(),
+        },
+    )
+}
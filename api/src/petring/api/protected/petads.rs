@@ -1,121 +1,255 @@
 use super::{
-    AdEditRequest, AdResponse, AdSubmission, BulkAdDeleteRequest, BulkAdDeleteResponse,
+    AdDailyStatResponse, AdEditRequest, AdExpiry, AdFormatQuery, AdResponse, AdScheduleRequest,
+    AdStatsQuery, AdStatsResponse, AdSubmission, AdWeightRequest, BulkAdDeleteRequest,
+    BulkAdDeleteResponse, ExpiringAdResponse, ExpiringAdsQuery, ExpiringAdsResponse,
+    audit::{self, Audit},
     database::{
-        ads,
-        entities::{AdModel, Ads, Users},
+        AdFormat, Status, UrlKind, ad_daily_stats, ads,
+        entities::{AdDailyStats, AdModel, Ads, Users},
         users,
     },
     petring_api_err, petring_api_response,
     state::AppState,
 };
+use crate::petring::{
+    ad_images::{self, ImageError, StoredImage},
+    bans, rotation, url_policy, username,
+};
 use axum::{
     Json,
-    extract::{Path, State},
-    http::StatusCode,
+    body::Body,
+    extract::{Multipart, Path, Query, State, multipart::MultipartError},
+    http::{Response, StatusCode},
     response::IntoResponse,
 };
-use chrono::Utc;
-use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, prelude::Expr};
+use chrono::{DateTime, Days, Duration as ChronoDuration, Utc};
+use sea_orm::{
+    ActiveEnum, ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QuerySelect, Set,
+    TransactionTrait,
+    sea_query::{Expr, SimpleExpr},
+};
+use serde_json::Value;
+use std::collections::HashMap;
 use tracing::{error, info};
 
-pub async fn post_ad_submit(
-    State(state): State<AppState>,
-    Json(submission): Json<AdSubmission>,
-) -> impl IntoResponse {
+// One of a member's ads. Without a format this only works for members that
+// have a single ad, which is what older clients expect.
+pub(super) async fn find_ad(
+    state: &AppState,
+    owner: SimpleExpr,
+    format: Option<AdFormat>,
+) -> Result<ads::Model, Response<Body>> {
+    let mut select = Ads::find().filter(owner);
+    if let Some(format) = format {
+        select = select.filter(ads::Column::Format.eq(format));
+    }
+
+    let mut ads = match select.limit(2).all(&state.db).await {
+        Ok(ads) => ads,
+        Err(_) => {
+            return Err(petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch ad",
+            ));
+        }
+    };
+
+    match ads.len() {
+        0 => Err(petring_api_err(StatusCode::NOT_FOUND, "Ad not found")),
+        1 => Ok(ads.remove(0)),
+        _ => Err(petring_api_err(
+            StatusCode::BAD_REQUEST,
+            "Member has more than one ad, pick a format",
+        )),
+    }
+}
+
+// Ads are looked up by the member's discord id, names go through
+// `username::key` so they match however they're written
+async fn member_discord_id(state: &AppState, username: &str) -> Result<i64, Response<Body>> {
+    match Users::find()
+        .filter(users::Column::UsernameKey.eq(username::key(username)))
+        .one(&state.db)
+        .await
+    {
+        Ok(Some(user)) => Ok(user.discord_id),
+        Ok(None) => Err(petring_api_err(StatusCode::NOT_FOUND, "Ad not found")),
+        Err(_) => Err(petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch user",
+        )),
+    }
+}
+
+// Where the image came from and the copy that gets served
+struct AdImage {
+    url: String,
+    canonical: String,
+    stored: StoredImage,
+}
+
+// Who the ad is for and the rejected ad it replaces, if any. Shared by
+// both ways of submitting an ad.
+async fn ad_owner(
+    state: &AppState,
+    discord_id: u64,
+    format: AdFormat,
+    image_urls: &[&str],
+) -> Result<(users::Model, Option<ads::Model>), Response<Body>> {
     let user = match Users::find()
-        .filter(users::Column::DiscordId.eq(submission.discord_id as i64))
+        .filter(users::Column::DiscordId.eq(discord_id as i64))
         .one(&state.db)
         .await
     {
         Ok(user) => user,
         Err(_) => {
-            return petring_api_err(
+            return Err(petring_api_err(
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Failed to fetch user, does it exist?",
-            );
+            ));
         }
     };
 
     let user = match user {
         Some(user) => user,
         None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
+            return Err(petring_api_err(StatusCode::NOT_FOUND, "User not found"));
         }
     };
 
-    if !user.verified {
-        return petring_api_err(StatusCode::NOT_FOUND, "User not verified");
+    if user.status != Status::Approved {
+        return Err(petring_api_err(StatusCode::NOT_FOUND, "User not approved"));
+    }
+
+    let mut urls = image_urls.to_vec();
+    urls.push(&user.url);
+
+    match bans::find(state, discord_id, &urls).await {
+        Ok(Some(ban)) => return Err(petring_api_err(StatusCode::FORBIDDEN, &bans::message(&ban))),
+        Ok(None) => {}
+        Err(_) => {
+            return Err(petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to check bans",
+            ));
+        }
     }
 
+    // A rejected ad can be sent again, it replaces the old one
     let does_ad_already_exist = Ads::find()
-        .filter(ads::Column::DiscordId.eq(submission.discord_id as i64))
+        .filter(ads::Column::DiscordId.eq(discord_id as i64))
+        .filter(ads::Column::Format.eq(format))
         .one(&state.db)
-        .await
-        .unwrap_or(None);
+        .await;
+
+    let rejected = match does_ad_already_exist {
+        Ok(Some(ad)) if ad.status == Status::Rejected => Some(ad),
+        Ok(Some(_)) => {
+            return Err(petring_api_err(
+                StatusCode::CONFLICT,
+                "Ad already exists for this format",
+            ));
+        }
+        Ok(None) => None,
+        Err(_) => {
+            return Err(petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch ad",
+            ));
+        }
+    };
+
+    Ok((user, rejected))
+}
 
-    if does_ad_already_exist.is_some() {
-        return petring_api_err(StatusCode::CONFLICT, "Ad already exists");
+async fn ensure_image_unused(
+    state: &AppState,
+    canonical: &str,
+    rejected: Option<&ads::Model>,
+) -> Result<(), Response<Body>> {
+    match Ads::find()
+        .filter(ads::Column::ImageUrlCanonical.eq(canonical))
+        .one(&state.db)
+        .await
+    {
+        Ok(Some(ad)) if Some(ad.id) != rejected.map(|rejected| rejected.id) => Err(
+            petring_api_err(StatusCode::CONFLICT, "Image already used by another ad"),
+        ),
+        Ok(_) => Ok(()),
+        Err(_) => Err(petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch ad",
+        )),
     }
+}
+
+async fn insert_ad(
+    state: &AppState,
+    audit: &Audit,
+    user: &users::Model,
+    format: AdFormat,
+    rejected: Option<ads::Model>,
+    image: AdImage,
+) -> Response<Body> {
+    let before = audit::snapshot(&rejected.as_ref().map(AdResponse::from));
 
-    let image_url = submission.image_url.clone();
-
-    let image_url_parts: Vec<&str> = image_url.split('/').collect();
-
-    let image_url_base_url = format!("{}/{}", image_url_parts[0], image_url_parts[1]);
-
-    let patterns = &[
-        "discord",
-        "localhost",
-        "127.0.0.1",
-        "discord",
-        "catbox",
-        "fileditch",
-        "imageshack",
-        "google",
-        "imgbb",
-        "gyazo",
-        "twitter",
-        "reddit",
-        "pixiv",
-        "tumblr",
-    ];
-
-    let image_url_invalid = patterns
-        .iter()
-        .any(|pattern| image_url_base_url.contains(pattern));
-
-    if image_url_invalid {
-        return petring_api_err(StatusCode::BAD_REQUEST, "Invalid image url");
+    // The rejected row only goes if the new one makes it in
+    let txn = match state.db.begin().await {
+        Ok(txn) => txn,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start transaction",
+            );
+        }
+    };
+
+    if let Some(rejected) = rejected
+        && Ads::delete_by_id(rejected.id).exec(&txn).await.is_err()
+    {
+        return petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to replace rejected ad",
+        );
     }
 
     let now = Utc::now().to_rfc3339();
 
     let db_submission = AdModel {
         username: Set(user.username.clone()),
-        discord_id: Set(submission.discord_id as i64),
-        image_url: Set(submission.image_url.clone()),
+        discord_id: Set(user.discord_id),
+        format: Set(format),
+        image_url: Set(image.url),
+        image_url_canonical: Set(image.canonical),
+        image_hash: Set(image.stored.hash),
+        image_width: Set(image.stored.width as i32),
+        image_height: Set(image.stored.height as i32),
         ad_url: Set(user.url.clone()),
-        verified: Set(false),
+        status: Set(Status::Pending),
         created_at: Set(now.clone()),
         edited_at: Set("".to_string()),
         verified_at: Set("".to_string()),
         ..Default::default()
     };
 
-    match db_submission.insert(&state.db).await {
-        Ok(_) => petring_api_response(
-            StatusCode::OK,
-            AdResponse {
-                username: user.username.clone(),
-                discord_id: submission.discord_id,
-                image_url: submission.image_url.clone(),
-                ad_url: user.url.clone(),
-                verified: false,
-                created_at: now,
-                edited_at: "".to_string(),
-                verified_at: "".to_string(),
-            },
-        ),
+    let inserted = match db_submission.insert(&txn).await {
+        Ok(inserted) => txn.commit().await.map(|_| inserted),
+        Err(e) => Err(e),
+    };
+
+    match inserted {
+        Ok(inserted) => {
+            let after = AdResponse::from(&inserted);
+            audit
+                .record(
+                    state,
+                    [user.discord_id as u64],
+                    before,
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
         Err(e) => petring_api_err(
             StatusCode::INTERNAL_SERVER_ERROR,
             &format!("Failed to insert ad: {e}"),
@@ -123,49 +257,263 @@ pub async fn post_ad_submit(
     }
 }
 
+pub async fn post_ad_submit(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(submission): Json<AdSubmission>,
+) -> impl IntoResponse {
+    let (user, rejected) = match ad_owner(
+        &state,
+        submission.discord_id,
+        submission.format,
+        &[&submission.image_url],
+    )
+    .await
+    {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+
+    let policy = match url_policy::load(&state).await {
+        Ok(policy) => policy,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load url policy",
+            );
+        }
+    };
+
+    let image_url = match policy.check(&submission.image_url, UrlKind::Image).await {
+        Ok(url) => url,
+        Err(e) => {
+            return petring_api_err(StatusCode::BAD_REQUEST, &format!("Invalid image url: {e}"));
+        }
+    };
+    let image_url_canonical = url_policy::canonical(&image_url);
+
+    if let Err(response) =
+        ensure_image_unused(&state, &image_url_canonical, rejected.as_ref()).await
+    {
+        return response;
+    }
+
+    let image = match ad_images::ingest(&state, &image_url, submission.format).await {
+        Ok(image) => image,
+        Err(e) => {
+            return petring_api_err(StatusCode::BAD_REQUEST, &format!("Invalid image: {e}"));
+        }
+    };
+
+    insert_ad(
+        &state,
+        &audit,
+        &user,
+        submission.format,
+        rejected,
+        AdImage {
+            url: submission.image_url,
+            canonical: image_url_canonical,
+            stored: image,
+        },
+    )
+    .await
+}
+
+/* POST /post/ad/upload
+ *
+ * For members without anywhere to host their image, the bot forwards the
+ * attachment as multipart with a `discord_id` and an `image` field, plus an
+ * optional `format`.
+ */
+
+// Bodies over the route's limit fail while reading, say why
+fn multipart_err(e: MultipartError, max_bytes: usize) -> Response<Body> {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        return petring_api_err(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!("Invalid image: {}", ImageError::TooLarge(max_bytes)),
+        );
+    }
+
+    petring_api_err(e.status(), &e.body_text())
+}
+
+pub async fn post_ad_upload(
+    State(state): State<AppState>,
+    audit: Audit,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let max_bytes = state.ad_images.max_bytes;
+    let mut discord_id = None;
+    let mut format = AdFormat::default();
+    let mut bytes = None;
+
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return multipart_err(e, max_bytes),
+        };
+
+        match field.name() {
+            Some("discord_id") => match field.text().await.map(|id| id.trim().parse::<u64>()) {
+                Ok(Ok(id)) => discord_id = Some(id),
+                _ => return petring_api_err(StatusCode::BAD_REQUEST, "Invalid discord_id"),
+            },
+            Some("format") => {
+                match field
+                    .text()
+                    .await
+                    .map(|value| AdFormat::try_from_value(&value.trim().to_string()))
+                {
+                    Ok(Ok(value)) => format = value,
+                    _ => return petring_api_err(StatusCode::BAD_REQUEST, "Invalid format"),
+                }
+            }
+            Some("image") => {
+                let mut image = Vec::new();
+                loop {
+                    match field.chunk().await {
+                        Ok(Some(chunk)) => image.extend_from_slice(&chunk),
+                        Ok(None) => break,
+                        Err(e) => return multipart_err(e, max_bytes),
+                    }
+
+                    if image.len() > max_bytes {
+                        return petring_api_err(
+                            StatusCode::PAYLOAD_TOO_LARGE,
+                            &format!("Invalid image: {}", ImageError::TooLarge(max_bytes)),
+                        );
+                    }
+                }
+                bytes = Some(image);
+            }
+            _ => {}
+        }
+    }
+
+    let (discord_id, bytes) = match (discord_id, bytes) {
+        (Some(discord_id), Some(bytes)) => (discord_id, bytes),
+        (None, _) => return petring_api_err(StatusCode::BAD_REQUEST, "Missing discord_id"),
+        (_, None) => return petring_api_err(StatusCode::BAD_REQUEST, "Missing image"),
+    };
+
+    let (user, rejected) = match ad_owner(&state, discord_id, format, &[]).await {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+
+    let image = match ad_images::store(&state, &bytes, format).await {
+        Ok(image) => image,
+        Err(e) => {
+            return petring_api_err(StatusCode::BAD_REQUEST, &format!("Invalid image: {e}"));
+        }
+    };
+
+    // Uploads have no url of their own, they're told apart by their contents
+    let image_url_canonical = format!("upload:{}", image.hash);
+    if let Err(response) =
+        ensure_image_unused(&state, &image_url_canonical, rejected.as_ref()).await
+    {
+        return response;
+    }
+
+    insert_ad(
+        &state,
+        &audit,
+        &user,
+        format,
+        rejected,
+        AdImage {
+            url: ad_images::local_url(&state, &image.hash),
+            canonical: image_url_canonical,
+            stored: image,
+        },
+    )
+    .await
+}
+
 pub async fn patch_ad_verify(
     State(state): State<AppState>,
+    audit: Audit,
     Path(discord_id): Path<u64>,
+    Query(query): Query<AdFormatQuery>,
 ) -> impl IntoResponse {
-    let ad = match Ads::find()
-        .filter(ads::Column::DiscordId.eq(discord_id as i64))
-        .one(&state.db)
-        .await
+    let ad = match find_ad(
+        &state,
+        ads::Column::DiscordId.eq(discord_id as i64),
+        query.format,
+    )
+    .await
     {
         Ok(ad) => ad,
-        Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ad");
+        Err(response) => return response,
+    };
+
+    let before = audit::snapshot(&AdResponse::from(&ad));
+    // Coming (back) into rotation starts level with the least shown ad. An ad
+    // that's already running keeps its count as is
+    let baseline = if ad.status == Status::Approved {
+        None
+    } else {
+        match rotation::eligible(&state).all(&state.db).await {
+            Ok(running) => Some(state.rotation.baseline(&running, ad.weight)),
+            Err(_) => {
+                return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ads");
+            }
         }
     };
 
-    let ad = match ad {
-        Some(ad) => ad,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "Ad not found");
+    let txn = match state.db.begin().await {
+        Ok(txn) => txn,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start transaction",
+            );
         }
     };
 
-    let now = Utc::now().to_rfc3339();
-    match Ads::update_many()
-        .col_expr(ads::Column::Verified, Expr::value(true))
-        .col_expr(ads::Column::VerifiedAt, Expr::value(now.clone()))
-        .filter(ads::Column::DiscordId.eq(discord_id as i64))
-        .exec(&state.db)
-        .await
+    // Raised in the query rather than written back, so impressions flushed
+    // since the ad was read aren't lost
+    if let Some(baseline) = baseline
+        && let Err(e) = Ads::update_many()
+            .col_expr(
+                ads::Column::Impressions,
+                Expr::cust_with_values(r#"MAX("impressions", ?)"#, [baseline]),
+            )
+            .filter(ads::Column::Id.eq(ad.id))
+            .exec(&txn)
+            .await
     {
-        Ok(_) => petring_api_response(
-            StatusCode::OK,
-            AdResponse {
-                username: ad.username.clone(),
-                discord_id: ad.discord_id as u64,
-                image_url: ad.image_url.clone(),
-                ad_url: ad.ad_url.clone(),
-                verified: true,
-                created_at: ad.created_at,
-                edited_at: ad.edited_at,
-                verified_at: now,
-            },
-        ),
+        return petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to verify ad: {e}"),
+        );
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let mut active_ad: AdModel = ad.into();
+    active_ad.status = Set(Status::Approved);
+    active_ad.status_reason = Set("".to_string());
+    active_ad.moderated_by = Set(audit.actor_id().map(|id| id as i64));
+    active_ad.moderated_at = Set(now.clone());
+    active_ad.verified_at = Set(now);
+
+    let verified = match active_ad.update(&txn).await {
+        Ok(verified) => txn.commit().await.map(|_| verified),
+        Err(e) => Err(e),
+    };
+
+    match verified {
+        Ok(verified) => {
+            let after = AdResponse::from(&verified);
+            audit
+                .record(&state, [discord_id], before, audit::snapshot(&after))
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
         Err(e) => petring_api_err(
             StatusCode::INTERNAL_SERVER_ERROR,
             &format!("Failed to verify ad: {e}"),
@@ -173,51 +521,336 @@ pub async fn patch_ad_verify(
     }
 }
 
-pub async fn patch_ad_edit(
+pub async fn patch_ad_weight(
     State(state): State<AppState>,
-    Json(submission): Json<AdEditRequest>,
+    audit: Audit,
+    Json(request): Json<AdWeightRequest>,
 ) -> impl IntoResponse {
-    let mut editing_url = false;
+    if !(1..=rotation::MAX_WEIGHT).contains(&request.weight) {
+        return petring_api_err(
+            StatusCode::BAD_REQUEST,
+            &format!("Weight has to be between 1 and {}", rotation::MAX_WEIGHT),
+        );
+    }
 
-    let patterns = &[
-        "discord",
-        "localhost",
-        "127.0.0.1",
-        "discord",
-        "catbox",
-        "fileditch",
-        "imageshack",
-        "google",
-        "imgbb",
-        "gyazo",
-        "twitter",
-        "reddit",
-        "pixiv",
-        "tumblr",
-    ];
-
-    let ad = match Ads::find()
-        .filter(ads::Column::DiscordId.eq(submission.discord_id))
-        .filter(ads::Column::Verified.eq(true))
-        .one(&state.db)
-        .await
+    let ad = match find_ad(
+        &state,
+        ads::Column::DiscordId.eq(request.discord_id as i64),
+        request.format,
+    )
+    .await
     {
         Ok(ad) => ad,
+        Err(response) => return response,
+    };
+
+    if ad.weight == request.weight {
+        return petring_api_err(StatusCode::NOT_MODIFIED, "No changes made");
+    }
+
+    let before = audit::snapshot(&AdResponse::from(&ad));
+    let mut active_ad: AdModel = ad.into();
+    active_ad.weight = Set(request.weight);
+
+    match active_ad.update(&state.db).await {
+        Ok(updated) => {
+            let after = AdResponse::from(&updated);
+            audit
+                .record(
+                    &state,
+                    [request.discord_id],
+                    before,
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update ad"),
+    }
+}
+
+// Dates are stored in UTC so they compare as strings
+fn schedule_date(value: &str) -> Result<Option<DateTime<Utc>>, &'static str> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+
+    DateTime::parse_from_rfc3339(value.trim())
+        .map(|date| Some(date.with_timezone(&Utc)))
+        .map_err(|_| "Invalid date, expected an RFC 3339 date")
+}
+
+fn stored_date(date: Option<DateTime<Utc>>) -> String {
+    date.map(|date| date.to_rfc3339()).unwrap_or_default()
+}
+
+/* PATCH /patch/ad/schedule
+ *
+ * Limits when an ad runs, for events and the like. The ad still has to be
+ * approved to be shown.
+ */
+
+pub async fn patch_ad_schedule(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(request): Json<AdScheduleRequest>,
+) -> impl IntoResponse {
+    if request.starts_at.is_none() && request.ends_at.is_none() {
+        return petring_api_err(StatusCode::NOT_MODIFIED, "No changes made");
+    }
+
+    let ad = match find_ad(
+        &state,
+        ads::Column::DiscordId.eq(request.discord_id as i64),
+        request.format,
+    )
+    .await
+    {
+        Ok(ad) => ad,
+        Err(response) => return response,
+    };
+
+    let starts_at = match request.starts_at.as_deref() {
+        Some(value) => schedule_date(value),
+        None => schedule_date(&ad.starts_at),
+    };
+    let ends_at = match request.ends_at.as_deref() {
+        Some(value) => schedule_date(value),
+        None => schedule_date(&ad.ends_at),
+    };
+    let (starts_at, ends_at) = match (starts_at, ends_at) {
+        (Ok(starts_at), Ok(ends_at)) => (starts_at, ends_at),
+        (Err(message), _) | (_, Err(message)) => {
+            return petring_api_err(StatusCode::BAD_REQUEST, message);
+        }
+    };
+
+    if request.ends_at.is_some()
+        && let Some(ends_at) = ends_at
+        && ends_at <= Utc::now()
+    {
+        return petring_api_err(StatusCode::BAD_REQUEST, "End date has to be in the future");
+    }
+
+    if let (Some(starts_at), Some(ends_at)) = (starts_at, ends_at)
+        && ends_at <= starts_at
+    {
+        return petring_api_err(
+            StatusCode::BAD_REQUEST,
+            "End date has to be after the start date",
+        );
+    }
+
+    let (starts_at, ends_at) = (stored_date(starts_at), stored_date(ends_at));
+    if starts_at == ad.starts_at && ends_at == ad.ends_at {
+        return petring_api_err(StatusCode::NOT_MODIFIED, "No changes made");
+    }
+
+    let before = audit::snapshot(&AdResponse::from(&ad));
+    let mut active_ad: AdModel = ad.into();
+    active_ad.starts_at = Set(starts_at);
+    active_ad.ends_at = Set(ends_at);
+
+    match active_ad.update(&state.db).await {
+        Ok(updated) => {
+            let after = AdResponse::from(&updated);
+            audit
+                .record(
+                    &state,
+                    [request.discord_id],
+                    before,
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update ad"),
+    }
+}
+
+/* GET /get/ads/expiring
+ *
+ * Approved ads that stop running within the next `days` days, either
+ * because their approval runs out or their campaign ends. Meant for the bot
+ * to remind owners.
+ */
+
+const DEFAULT_EXPIRING_DAYS: u32 = 7;
+const MAX_EXPIRING_DAYS: u32 = 90;
+
+pub async fn get_expiring_ads(
+    State(state): State<AppState>,
+    Query(query): Query<ExpiringAdsQuery>,
+) -> impl IntoResponse {
+    let days = match query.days {
+        Some(0) => return petring_api_err(StatusCode::BAD_REQUEST, "Invalid days"),
+        Some(days) => days.min(MAX_EXPIRING_DAYS),
+        None => DEFAULT_EXPIRING_DAYS,
+    };
+
+    let ads = match Ads::find()
+        .filter(ads::Column::Status.eq(Status::Approved))
+        .all(&state.db)
+        .await
+    {
+        Ok(ads) => ads,
         Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ad");
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ads");
         }
     };
 
-    let ad = match ad {
-        Some(ad) => ad,
-        None => {
+    let now = Utc::now();
+    let horizon = now + ChronoDuration::days(days as i64);
+
+    let mut expiring: Vec<(DateTime<Utc>, AdExpiry, ads::Model)> = ads
+        .into_iter()
+        .filter_map(|ad| {
+            let approval = rotation::approval_expiry(&state, &ad)
+                .map(|expires_at| (expires_at, AdExpiry::Approval));
+            let campaign = schedule_date(&ad.ends_at)
+                .ok()
+                .flatten()
+                .map(|ends_at| (ends_at, AdExpiry::Campaign));
+
+            // Whichever comes first, an ad that already stopped isn't expiring
+            let (expires_at, reason) = [approval, campaign]
+                .into_iter()
+                .flatten()
+                .filter(|(expires_at, _)| *expires_at > now)
+                .min_by_key(|(expires_at, _)| *expires_at)?;
+
+            (expires_at <= horizon).then_some((expires_at, reason, ad))
+        })
+        .collect();
+    expiring.sort_by_key(|(expires_at, _, ad)| (*expires_at, ad.id));
+
+    petring_api_response(
+        StatusCode::OK,
+        ExpiringAdsResponse {
+            days,
+            ads: expiring
+                .into_iter()
+                .map(|(expires_at, reason, ad)| ExpiringAdResponse {
+                    expires_at: expires_at.to_rfc3339(),
+                    reason,
+                    ad: AdResponse::from(&ad),
+                })
+                .collect(),
+        },
+    )
+}
+
+/* GET /get/ad/{discord_id}/stats
+ *
+ * Impressions and clicks per UTC day for the last `days` days, today
+ * included. Repeat views and clicks from the same visitor within half an
+ * hour only count once.
+ */
+
+const DEFAULT_STATS_DAYS: u32 = 30;
+const MAX_STATS_DAYS: u32 = 365;
+
+pub async fn get_ad_stats(
+    State(state): State<AppState>,
+    Path(discord_id): Path<u64>,
+    Query(query): Query<AdStatsQuery>,
+) -> impl IntoResponse {
+    let days = match query.days {
+        Some(0) => return petring_api_err(StatusCode::BAD_REQUEST, "Invalid days"),
+        Some(days) => days.min(MAX_STATS_DAYS),
+        None => DEFAULT_STATS_DAYS,
+    };
+
+    let ad = match find_ad(
+        &state,
+        ads::Column::DiscordId.eq(discord_id as i64),
+        query.format,
+    )
+    .await
+    {
+        Ok(ad) => ad,
+        Err(response) => return response,
+    };
+
+    let today = Utc::now().date_naive();
+    let first = today - Days::new(days as u64 - 1);
+
+    let rows = match AdDailyStats::find()
+        .filter(ad_daily_stats::Column::AdId.eq(ad.id))
+        .filter(ad_daily_stats::Column::Day.gte(first.format("%Y-%m-%d").to_string()))
+        .all(&state.db)
+        .await
+    {
+        Ok(rows) => rows,
+        Err(_) => {
             return petring_api_err(
-                StatusCode::NOT_FOUND,
-                "Ad not found, are you sure it's verified?",
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch ad stats",
             );
         }
     };
 
+    let mut by_day: HashMap<String, ad_daily_stats::Model> =
+        rows.into_iter().map(|row| (row.day.clone(), row)).collect();
+
+    let daily: Vec<AdDailyStatResponse> = first
+        .iter_days()
+        .take(days as usize)
+        .map(|day| {
+            let day = day.format("%Y-%m-%d").to_string();
+            match by_day.remove(&day) {
+                Some(row) => AdDailyStatResponse {
+                    day,
+                    impressions: row.impressions,
+                    clicks: row.clicks,
+                },
+                None => AdDailyStatResponse {
+                    day,
+                    impressions: 0,
+                    clicks: 0,
+                },
+            }
+        })
+        .collect();
+
+    petring_api_response(
+        StatusCode::OK,
+        AdStatsResponse {
+            discord_id,
+            format: ad.format,
+            days,
+            impressions: daily.iter().map(|day| day.impressions).sum(),
+            clicks: daily.iter().map(|day| day.clicks).sum(),
+            daily,
+        },
+    )
+}
+
+pub async fn patch_ad_edit(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(submission): Json<AdEditRequest>,
+) -> impl IntoResponse {
+    let mut editing_url = false;
+
+    let ad = match find_ad(
+        &state,
+        ads::Column::DiscordId.eq(submission.discord_id as i64),
+        submission.format,
+    )
+    .await
+    {
+        Ok(ad) => ad,
+        Err(response) => return response,
+    };
+
+    if ad.status != Status::Approved {
+        return petring_api_err(
+            StatusCode::NOT_FOUND,
+            "Ad not found, are you sure it's approved?",
+        );
+    }
+
     let url = if let Some(url) = submission.url {
         editing_url = true;
         url
@@ -230,37 +863,85 @@ pub async fn patch_ad_edit(
         let now = Utc::now().to_rfc3339();
 
         if editing_url && ad.image_url != url {
-            let url_parts: Vec<&str> = url.split('/').collect();
-            let url_base_url = format!("{}/{}", url_parts[0], url_parts[1]);
-            let url_invalid = patterns
-                .iter()
-                .any(|pattern| url_base_url.contains(pattern));
-
-            if url_invalid {
-                return petring_api_err(StatusCode::BAD_REQUEST, "Invalid url");
+            let policy = match url_policy::load(&state).await {
+                Ok(policy) => policy,
+                Err(_) => {
+                    return petring_api_err(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to load url policy",
+                    );
+                }
+            };
+
+            let image_url = match policy.check(&url, UrlKind::Image).await {
+                Ok(url) => url,
+                Err(e) => {
+                    return petring_api_err(StatusCode::BAD_REQUEST, &format!("Invalid url: {e}"));
+                }
+            };
+            let canonical = url_policy::canonical(&image_url);
+
+            if canonical != ad.image_url_canonical {
+                match Ads::find()
+                    .filter(ads::Column::ImageUrlCanonical.eq(canonical.clone()))
+                    .one(&state.db)
+                    .await
+                {
+                    Ok(Some(_)) => {
+                        return petring_api_err(
+                            StatusCode::CONFLICT,
+                            "Image already used by another ad",
+                        );
+                    }
+                    Ok(None) => {}
+                    Err(_) => {
+                        return petring_api_err(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "Failed to fetch ad",
+                        );
+                    }
+                }
             }
 
+            let image = match ad_images::ingest(&state, &image_url, ad.format).await {
+                Ok(image) => image,
+                Err(e) => {
+                    return petring_api_err(
+                        StatusCode::BAD_REQUEST,
+                        &format!("Invalid image: {e}"),
+                    );
+                }
+            };
+
             active_ad.image_url = Set(url.clone());
+            active_ad.image_url_canonical = Set(canonical);
+            active_ad.image_hash = Set(image.hash);
+            active_ad.image_width = Set(image.width as i32);
+            active_ad.image_height = Set(image.height as i32);
+
+            // A new image hasn't been looked at, it goes back in the queue
+            active_ad.status = Set(Status::Pending);
+            active_ad.status_reason = Set("Image changed".to_string());
+            active_ad.verified_at = Set(String::new());
+            active_ad.moderated_by = Set(None);
+            active_ad.moderated_at = Set(now.clone());
         }
 
         active_ad.edited_at = Set(now.clone());
 
         match active_ad.update(&state.db).await {
-            Ok(_) => {
+            Ok(updated) => {
                 info!("Updated ad for {}", ad.username);
-                return petring_api_response(
-                    StatusCode::OK,
-                    AdResponse {
-                        username: ad.username,
-                        discord_id: ad.discord_id as u64,
-                        image_url: url,
-                        ad_url: ad.ad_url,
-                        verified: ad.verified,
-                        created_at: ad.created_at,
-                        edited_at: now,
-                        verified_at: ad.verified_at,
-                    },
-                );
+                let after = AdResponse::from(&updated);
+                audit
+                    .record(
+                        &state,
+                        [ad.discord_id as u64],
+                        audit::snapshot(&AdResponse::from(&ad)),
+                        audit::snapshot(&after),
+                    )
+                    .await;
+                return petring_api_response(StatusCode::OK, after);
             }
             Err(err) => {
                 error!("Failed to update ad for {}: {err}", ad.username);
@@ -274,98 +955,87 @@ pub async fn patch_ad_edit(
 
 pub async fn delete_ad_by_discord_id(
     State(state): State<AppState>,
+    audit: Audit,
     Path(discord_id): Path<u64>,
+    Query(query): Query<AdFormatQuery>,
 ) -> impl IntoResponse {
-    let ad = match Ads::find()
-        .filter(ads::Column::DiscordId.eq(discord_id as i64))
-        .one(&state.db)
-        .await
+    let ad = match find_ad(
+        &state,
+        ads::Column::DiscordId.eq(discord_id as i64),
+        query.format,
+    )
+    .await
     {
         Ok(ad) => ad,
-        Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ad");
-        }
-    };
-
-    let ad = match ad {
-        Some(ad) => ad,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "Ad not found");
-        }
+        Err(response) => return response,
     };
 
     match Ads::delete_by_id(ad.id).exec(&state.db).await {
-        Ok(_) => petring_api_response(
-            StatusCode::OK,
-            AdResponse {
-                username: ad.username.clone(),
-                discord_id: ad.discord_id as u64,
-                image_url: ad.image_url.clone(),
-                ad_url: ad.ad_url.clone(),
-                verified: ad.verified,
-                created_at: ad.created_at,
-                edited_at: ad.edited_at,
-                verified_at: ad.verified_at,
-            },
-        ),
+        Ok(_) => {
+            let before = AdResponse::from(&ad);
+            audit
+                .record(
+                    &state,
+                    [ad.discord_id as u64],
+                    audit::snapshot(&before),
+                    Value::Null,
+                )
+                .await;
+            petring_api_response(StatusCode::OK, before)
+        }
         Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete ad"),
     }
 }
 
 pub async fn delete_ad_by_username(
     State(state): State<AppState>,
+    audit: Audit,
     Path(username): Path<String>,
+    Query(query): Query<AdFormatQuery>,
 ) -> impl IntoResponse {
-    let ad = match Ads::find()
-        .filter(ads::Column::Username.eq(username))
-        .one(&state.db)
-        .await
-    {
-        Ok(ad) => ad,
-        Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ad");
-        }
+    let discord_id = match member_discord_id(&state, &username).await {
+        Ok(discord_id) => discord_id,
+        Err(response) => return response,
     };
 
-    let ad = match ad {
-        Some(ad) => ad,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "Ad not found");
-        }
+    let ad = match find_ad(&state, ads::Column::DiscordId.eq(discord_id), query.format).await {
+        Ok(ad) => ad,
+        Err(response) => return response,
     };
 
     match Ads::delete_by_id(ad.id).exec(&state.db).await {
-        Ok(_) => petring_api_response(
-            StatusCode::OK,
-            AdResponse {
-                username: ad.username.clone(),
-                discord_id: ad.discord_id as u64,
-                image_url: ad.image_url.clone(),
-                ad_url: ad.ad_url.clone(),
-                verified: ad.verified,
-                created_at: ad.created_at,
-                edited_at: ad.edited_at,
-                verified_at: ad.verified_at,
-            },
-        ),
+        Ok(_) => {
+            let before = AdResponse::from(&ad);
+            audit
+                .record(
+                    &state,
+                    [ad.discord_id as u64],
+                    audit::snapshot(&before),
+                    Value::Null,
+                )
+                .await;
+            petring_api_response(StatusCode::OK, before)
+        }
         Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete ad"),
     }
 }
 
 pub async fn bulk_delete_ads(
     State(state): State<AppState>,
+    audit: Audit,
     Json(bulk_delete_request): Json<BulkAdDeleteRequest>,
 ) -> impl IntoResponse {
     let mut ads_to_delete = Vec::new();
 
     if let Some(discord_ids) = bulk_delete_request.discord_ids {
         for discord_id in discord_ids {
-            let ad = match Ads::find()
+            // Every format the member has
+            let ads = match Ads::find()
                 .filter(ads::Column::DiscordId.eq(discord_id as i64))
-                .one(&state.db)
+                .all(&state.db)
                 .await
             {
-                Ok(ad) => ad,
+                Ok(ads) => ads,
                 Err(_) => {
                     return petring_api_err(
                         StatusCode::INTERNAL_SERVER_ERROR,
@@ -374,24 +1044,27 @@ pub async fn bulk_delete_ads(
                 }
             };
 
-            let ad = match ad {
-                Some(ad) => ad,
-                None => {
-                    return petring_api_err(StatusCode::NOT_FOUND, "Ad not found");
-                }
-            };
+            if ads.is_empty() {
+                return petring_api_err(StatusCode::NOT_FOUND, "Ad not found");
+            }
 
-            ads_to_delete.push(ad);
+            ads_to_delete.extend(ads);
         }
     }
     if let Some(usernames) = bulk_delete_request.usernames {
         for username in usernames {
-            let ad = match Ads::find()
-                .filter(ads::Column::Username.eq(username))
-                .one(&state.db)
+            let discord_id = match member_discord_id(&state, &username).await {
+                Ok(discord_id) => discord_id,
+                Err(response) => return response,
+            };
+
+            // Every format the member has
+            let ads = match Ads::find()
+                .filter(ads::Column::DiscordId.eq(discord_id))
+                .all(&state.db)
                 .await
             {
-                Ok(ad) => ad,
+                Ok(ads) => ads,
                 Err(_) => {
                     return petring_api_err(
                         StatusCode::INTERNAL_SERVER_ERROR,
@@ -400,14 +1073,13 @@ pub async fn bulk_delete_ads(
                 }
             };
 
-            let ad = match ad {
-                Some(ad) => ad,
-                None => {
-                    return petring_api_err(StatusCode::NOT_FOUND, "Ad not found");
+            if ads.is_empty() {
+                return petring_api_err(StatusCode::NOT_FOUND, "Ad not found");
+            }
+            for ad in ads {
+                if !ads_to_delete.contains(&ad) {
+                    ads_to_delete.push(ad);
                 }
-            };
-            if !ads_to_delete.contains(&ad) {
-                ads_to_delete.push(ad);
             }
         }
     }
@@ -417,21 +1089,33 @@ pub async fn bulk_delete_ads(
         .exec(&state.db)
         .await
     {
-        Ok(_) => petring_api_response(
-            StatusCode::OK,
-            BulkAdDeleteResponse {
-                message: "Ads deleted".to_string(),
-                discord_ids: ads_to_delete
-                    .iter()
-                    .map(|ad| ad.discord_id as u64)
-                    .collect(),
-                usernames: ads_to_delete.iter().map(|ad| ad.username.clone()).collect(),
-                image_urls: ads_to_delete
-                    .iter()
-                    .map(|ad| ad.image_url.clone())
-                    .collect(),
-            },
-        ),
+        Ok(_) => {
+            let before: Vec<AdResponse> = ads_to_delete.iter().map(AdResponse::from).collect();
+            audit
+                .record(
+                    &state,
+                    ads_to_delete.iter().map(|ad| ad.discord_id as u64),
+                    audit::snapshot(&before),
+                    Value::Null,
+                )
+                .await;
+
+            petring_api_response(
+                StatusCode::OK,
+                BulkAdDeleteResponse {
+                    message: "Ads deleted".to_string(),
+                    discord_ids: ads_to_delete
+                        .iter()
+                        .map(|ad| ad.discord_id as u64)
+                        .collect(),
+                    usernames: ads_to_delete.iter().map(|ad| ad.username.clone()).collect(),
+                    image_urls: ads_to_delete
+                        .iter()
+                        .map(|ad| ad.image_url.clone())
+                        .collect(),
+                },
+            )
+        }
         Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete ads"),
     }
 }
@@ -1,21 +1,38 @@
 use super::{
-    BulkUserDeleteRequest, BulkUserDeleteResponse, EditUserResponse, UserEdit, UserResponse,
-    UserSubmission,
+    BacklinkResponse, BulkUserDeleteRequest, BulkUserDeleteResponse, EditUserResponse,
+    ProfileFields, RingMoveRequest, RingOrderEntry, RingOrderRequest, RingOrderResponse,
+    RingSwapRequest, UnverifiedUserResponse, UserEdit, UserListResponse, UserResponse,
+    UserSubmission, UserVisibilityRequest, UserVisibilityResponse,
+    audit::{self, Audit},
+    backlinks,
     database::{
+        Status, UrlKind,
         entities::{UserModel, Users},
         users,
     },
-    petring_api_err, petring_api_response,
+    directory::{self, ListOptions},
+    list_error_response, petring_api_err, petring_api_response,
+    ring::Scope,
     state::AppState,
 };
+use crate::petring::{
+    bans,
+    url_policy::{self, UrlPolicy},
+    username,
+};
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
 };
 use chrono::Utc;
-use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, prelude::Expr};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    Set, TransactionTrait, prelude::Expr,
+};
+use serde_json::Value;
+use std::collections::HashSet;
 #[allow(unused_imports)]
 use tracing::{debug, error, info};
 
@@ -44,22 +61,11 @@ pub async fn get_user_by_discord_id(
         }
     };
 
-    if !user_by_discord.verified {
-        return petring_api_err(StatusCode::NOT_FOUND, "User not verified");
+    if user_by_discord.status != Status::Approved {
+        return petring_api_err(StatusCode::NOT_FOUND, "User not approved");
     }
 
-    petring_api_response(
-        StatusCode::OK,
-        UserResponse {
-            username: user_by_discord.username.clone(),
-            url: user_by_discord.url.clone(),
-            discord_id: user_by_discord.discord_id as u64,
-            verified: user_by_discord.verified,
-            created_at: user_by_discord.created_at,
-            edited_at: user_by_discord.edited_at,
-            verified_at: user_by_discord.verified_at,
-        },
-    )
+    petring_api_response(StatusCode::OK, UserResponse::from(&user_by_discord))
 }
 
 pub async fn get_user_by_discord_id_unverified(
@@ -86,24 +92,90 @@ pub async fn get_user_by_discord_id_unverified(
 
     petring_api_response(
         StatusCode::OK,
-        UserResponse {
-            username: user_by_discord.username.clone(),
-            url: user_by_discord.url.clone(),
-            discord_id: user_by_discord.discord_id as u64,
-            verified: false,
-            created_at: user_by_discord.created_at,
-            edited_at: user_by_discord.edited_at,
-            verified_at: user_by_discord.verified_at,
+        UnverifiedUserResponse {
+            backlinks: BacklinkResponse::from(&user_by_discord),
+            user: UserResponse::from(&user_by_discord),
         },
     )
 }
 
+/* GET /get/users/all
+ *
+ * Every member regardless of status, `status=pending` and friends narrow
+ * it down. The moderation queue itself lives at `/get/queue`.
+ */
+
+pub async fn get_users(
+    State(state): State<AppState>,
+    Query(options): Query<ListOptions>,
+) -> impl IntoResponse {
+    let mut select = Users::find();
+    if let Some(status) = options.status {
+        select = select.filter(users::Column::Status.eq(status));
+    }
+
+    match directory::list(&state, Scope::Default, select, &options).await {
+        Ok(page) => petring_api_response(
+            StatusCode::OK,
+            UserListResponse {
+                users: page.users.iter().map(UserResponse::from).collect(),
+                next_cursor: page.next_cursor,
+            },
+        ),
+        Err(e) => list_error_response(e),
+    }
+}
+
+pub async fn patch_user_backlinks(
+    State(state): State<AppState>,
+    audit: Audit,
+    Path(discord_id): Path<u64>,
+) -> impl IntoResponse {
+    let user = match Users::find()
+        .filter(users::Column::DiscordId.eq(discord_id as i64))
+        .one(&state.db)
+        .await
+    {
+        Ok(user) => user,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+        }
+    };
+
+    let user = match user {
+        Some(user) => user,
+        None => {
+            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
+        }
+    };
+
+    match backlinks::verify(&state, &user).await {
+        Ok(verified) => {
+            let after = BacklinkResponse::from(&verified);
+            audit
+                .record(
+                    &state,
+                    [discord_id],
+                    audit::snapshot(&BacklinkResponse::from(&user)),
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(_) => petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to store backlinks",
+        ),
+    }
+}
+
 pub async fn delete_user_by_username(
     State(state): State<AppState>,
+    audit: Audit,
     Path(username): Path<String>,
 ) -> impl IntoResponse {
     let user = match Users::find()
-        .filter(users::Column::Username.eq(username))
+        .filter(users::Column::UsernameKey.eq(username::key(&username)))
         .one(&state.db)
         .await
     {
@@ -121,24 +193,25 @@ pub async fn delete_user_by_username(
     };
 
     match Users::delete_by_id(user.id).exec(&state.db).await {
-        Ok(_) => petring_api_response(
-            StatusCode::OK,
-            UserResponse {
-                username: user.username.clone(),
-                url: user.url.clone(),
-                discord_id: user.discord_id as u64,
-                verified: user.verified,
-                created_at: user.created_at,
-                edited_at: user.edited_at,
-                verified_at: user.verified_at,
-            },
-        ),
+        Ok(_) => {
+            let before = UserResponse::from(&user);
+            audit
+                .record(
+                    &state,
+                    [user.discord_id as u64],
+                    audit::snapshot(&before),
+                    Value::Null,
+                )
+                .await;
+            petring_api_response(StatusCode::OK, before)
+        }
         Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete user"),
     }
 }
 
 pub async fn delete_user_by_discord_id(
     State(state): State<AppState>,
+    audit: Audit,
     Path(discord_id): Path<u64>,
 ) -> impl IntoResponse {
     let user = match Users::find()
@@ -160,24 +233,25 @@ pub async fn delete_user_by_discord_id(
     };
 
     match Users::delete_by_id(user.id).exec(&state.db).await {
-        Ok(_) => petring_api_response(
-            StatusCode::OK,
-            UserResponse {
-                username: user.username.clone(),
-                url: user.url.clone(),
-                discord_id: user.discord_id as u64,
-                verified: user.verified,
-                created_at: user.created_at,
-                edited_at: user.edited_at,
-                verified_at: user.verified_at,
-            },
-        ),
+        Ok(_) => {
+            let before = UserResponse::from(&user);
+            audit
+                .record(
+                    &state,
+                    [user.discord_id as u64],
+                    audit::snapshot(&before),
+                    Value::Null,
+                )
+                .await;
+            petring_api_response(StatusCode::OK, before)
+        }
         Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete user"),
     }
 }
 
 pub async fn bulk_delete_users(
     State(state): State<AppState>,
+    audit: Audit,
     Json(bulk_delete_request): Json<BulkUserDeleteRequest>,
 ) -> impl IntoResponse {
     let mut users_to_delete = Vec::new();
@@ -212,7 +286,7 @@ pub async fn bulk_delete_users(
     if let Some(usernames) = bulk_delete_request.usernames {
         for username in usernames {
             let user = match Users::find()
-                .filter(users::Column::Username.eq(username))
+                .filter(users::Column::UsernameKey.eq(username::key(&username)))
                 .one(&state.db)
                 .await
             {
@@ -242,26 +316,40 @@ pub async fn bulk_delete_users(
         .exec(&state.db)
         .await
     {
-        Ok(_) => petring_api_response(
-            StatusCode::OK,
-            BulkUserDeleteResponse {
-                message: "Users deleted".to_string(),
-                discord_ids: users_to_delete
-                    .iter()
-                    .map(|user| user.discord_id as u64)
-                    .collect(),
-                usernames: users_to_delete
-                    .iter()
-                    .map(|user| user.username.clone())
-                    .collect(),
-            },
-        ),
+        Ok(_) => {
+            let before: Vec<UserResponse> =
+                users_to_delete.iter().map(UserResponse::from).collect();
+            audit
+                .record(
+                    &state,
+                    users_to_delete.iter().map(|user| user.discord_id as u64),
+                    audit::snapshot(&before),
+                    Value::Null,
+                )
+                .await;
+
+            petring_api_response(
+                StatusCode::OK,
+                BulkUserDeleteResponse {
+                    message: "Users deleted".to_string(),
+                    discord_ids: users_to_delete
+                        .iter()
+                        .map(|user| user.discord_id as u64)
+                        .collect(),
+                    usernames: users_to_delete
+                        .iter()
+                        .map(|user| user.username.clone())
+                        .collect(),
+                },
+            )
+        }
         Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete users"),
     }
 }
 
 pub async fn patch_user_verify(
     State(state): State<AppState>,
+    audit: Audit,
     Path(discord_user_id): Path<u64>,
 ) -> impl IntoResponse {
     let user = match Users::find()
@@ -282,86 +370,236 @@ pub async fn patch_user_verify(
         }
     };
 
+    let before = audit::snapshot(&UserResponse::from(&user));
     let now = Utc::now().to_rfc3339();
+    let mut active_user: UserModel = user.into();
+    active_user.status = Set(Status::Approved);
+    active_user.status_reason = Set("".to_string());
+    active_user.moderated_by = Set(audit.actor_id().map(|id| id as i64));
+    active_user.moderated_at = Set(now.clone());
+    active_user.verified_at = Set(now);
+
+    match active_user.update(&state.db).await {
+        Ok(verified) => {
+            let after = UserResponse::from(&verified);
+            audit
+                .record(&state, [discord_user_id], before, audit::snapshot(&after))
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update user"),
+    }
+}
+
+pub async fn patch_user_visibility(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(visibility): Json<UserVisibilityRequest>,
+) -> impl IntoResponse {
+    let user = match Users::find()
+        .filter(users::Column::DiscordId.eq(visibility.discord_id as i64))
+        .one(&state.db)
+        .await
+    {
+        Ok(user) => user,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+        }
+    };
+
+    let user = match user {
+        Some(user) => user,
+        None => {
+            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
+        }
+    };
+
     match Users::update_many()
-        .col_expr(users::Column::Verified, Expr::value(true))
-        .col_expr(users::Column::VerifiedAt, Expr::value(now.clone()))
-        .filter(users::Column::DiscordId.eq(discord_user_id as i64))
+        .col_expr(users::Column::Hidden, Expr::value(visibility.hidden))
+        .filter(users::Column::Id.eq(user.id))
         .exec(&state.db)
         .await
     {
-        Ok(_) => petring_api_response(
-            StatusCode::OK,
-            UserResponse {
+        Ok(_) => {
+            let after = UserVisibilityResponse {
                 username: user.username.clone(),
-                url: user.url.clone(),
                 discord_id: user.discord_id as u64,
-                verified: true,
-                created_at: user.created_at,
-                edited_at: user.edited_at,
-                verified_at: now,
-            },
-        ),
+                hidden: visibility.hidden,
+            };
+            audit
+                .record(
+                    &state,
+                    [visibility.discord_id],
+                    audit::snapshot(&UserVisibilityResponse {
+                        hidden: user.hidden,
+                        ..after.clone()
+                    }),
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
         Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update user"),
     }
 }
 
 pub async fn post_user_submit(
     State(state): State<AppState>,
+    audit: Audit,
     Json(submission): Json<UserSubmission>,
 ) -> impl IntoResponse {
-    let sanitize_username = submission
-        .username
-        .clone()
-        .replace(" ", "_")
-        .replace(".", "_");
-
-    let user = Users::find()
-        .filter(users::Column::Username.eq(sanitize_username.clone()))
+    let username = match username::validate(&submission.username) {
+        Ok(username) => username,
+        Err(e) => {
+            return petring_api_err(StatusCode::BAD_REQUEST, &format!("Invalid username: {e}"));
+        }
+    };
+    let username_key = username::key(&username);
+
+    // A rejected submission can be sent again, it replaces the old one
+    let rejected = match Users::find()
+        .filter(users::Column::DiscordId.eq(submission.discord_id as i64))
         .one(&state.db)
         .await
-        .unwrap_or(None);
+    {
+        Ok(Some(user)) if user.status == Status::Rejected => Some(user),
+        Ok(Some(_)) => return petring_api_err(StatusCode::CONFLICT, "User already exists"),
+        Ok(None) => None,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+        }
+    };
 
-    if user.is_some() {
-        return petring_api_err(StatusCode::CONFLICT, "User already exists");
+    match username::taken(
+        &state.db,
+        &username_key,
+        rejected.as_ref().map(|rejected| rejected.id),
+    )
+    .await
+    {
+        Ok(true) => return petring_api_err(StatusCode::CONFLICT, "User already exists"),
+        Ok(false) => {}
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+        }
     }
 
-    let user = Users::find()
-        .filter(users::Column::DiscordId.eq(submission.discord_id as i64))
+    let mut urls = submission.profile.image_urls();
+    urls.push(&submission.url);
+
+    match bans::find(&state, submission.discord_id, &urls).await {
+        Ok(Some(ban)) => return petring_api_err(StatusCode::FORBIDDEN, &bans::message(&ban)),
+        Ok(None) => {}
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to check bans");
+        }
+    }
+
+    let policy = match url_policy::load(&state).await {
+        Ok(policy) => policy,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load url policy",
+            );
+        }
+    };
+
+    let url_canonical = match policy.check(&submission.url, UrlKind::Site).await {
+        Ok(url) => url_policy::canonical(&url),
+        Err(e) => {
+            return petring_api_err(StatusCode::BAD_REQUEST, &format!("Invalid url: {e}"));
+        }
+    };
+
+    match Users::find()
+        .filter(users::Column::UrlCanonical.eq(url_canonical.clone()))
         .one(&state.db)
         .await
-        .unwrap_or(None);
-
-    if user.is_some() {
-        return petring_api_err(StatusCode::CONFLICT, "User already exists");
+    {
+        Ok(Some(user)) if Some(user.id) != rejected.as_ref().map(|rejected| rejected.id) => {
+            return petring_api_err(StatusCode::CONFLICT, "Site already in the ring");
+        }
+        Ok(_) => {}
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+        }
     }
 
+    let position = match Users::find()
+        .order_by_desc(users::Column::Position)
+        .one(&state.db)
+        .await
+    {
+        Ok(Some(last)) => last.position + 1,
+        Ok(None) => 1,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+        }
+    };
+
     let now = Utc::now().to_rfc3339();
 
-    let db_submission = UserModel {
-        username: Set(submission.username.clone()),
+    let mut db_submission = UserModel {
+        username: Set(username),
+        username_key: Set(username_key),
         discord_id: Set(submission.discord_id as i64),
         url: Set(submission.url.clone()),
-        verified: Set(false),
+        url_canonical: Set(url_canonical),
+        status: Set(Status::Pending),
         created_at: Set(now.clone()),
         edited_at: Set("".to_string()),
         verified_at: Set("".to_string()),
+        position: Set(position),
         ..Default::default()
     };
 
-    match db_submission.insert(&state.db).await {
-        Ok(_) => petring_api_response(
-            StatusCode::OK,
-            UserResponse {
-                username: submission.username.clone(),
-                discord_id: submission.discord_id,
-                url: submission.url.clone(),
-                verified: false,
-                created_at: now,
-                edited_at: "".to_string(),
-                verified_at: "".to_string(),
-            },
-        ),
+    if let Err(message) = apply_profile(&policy, &mut db_submission, submission.profile).await {
+        return petring_api_err(StatusCode::BAD_REQUEST, &message);
+    }
+
+    let before = audit::snapshot(&rejected.as_ref().map(UserResponse::from));
+
+    // The rejected row only goes if the new one makes it in
+    let txn = match state.db.begin().await {
+        Ok(txn) => txn,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start transaction",
+            );
+        }
+    };
+
+    if let Some(rejected) = rejected
+        && Users::delete_by_id(rejected.id).exec(&txn).await.is_err()
+    {
+        return petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to replace rejected user",
+        );
+    }
+
+    let inserted = match db_submission.insert(&txn).await {
+        Ok(inserted) => txn.commit().await.map(|_| inserted),
+        Err(e) => Err(e),
+    };
+
+    match inserted {
+        Ok(inserted) => {
+            let response = UserResponse::from(&inserted);
+            audit
+                .record(
+                    &state,
+                    [submission.discord_id],
+                    before,
+                    audit::snapshot(&response),
+                )
+                .await;
+            backlinks::spawn_verify(state.clone(), inserted);
+
+            petring_api_response(StatusCode::OK, response)
+        }
         Err(e) => petring_api_err(
             StatusCode::INTERNAL_SERVER_ERROR,
             &format!("Failed to insert user: {e}"),
@@ -371,36 +609,12 @@ pub async fn post_user_submit(
 
 pub async fn patch_user_edit(
     State(state): State<AppState>,
+    audit: Audit,
     Json(submission): Json<UserEdit>,
 ) -> impl IntoResponse {
     let mut editing_name = false;
     let mut editing_url = false;
 
-    let patterns = &[
-        "discord",
-        "localhost",
-        "127.0.0.1",
-        "google",
-        "twitter",
-        "x.com",
-        "reddit",
-        "pixiv",
-        "tumblr",
-        "facebook",
-        "instagram",
-        "youtube",
-        "tiktok",
-        "snapchat",
-        "pinterest",
-        "github",
-        "gitlab",
-        "bitbucket",
-        "medium",
-        "linkedin",
-        "stackoverflow",
-        "stackexchange",
-    ];
-
     let user = match Users::find()
         .filter(users::Column::DiscordId.eq(submission.discord_id))
         .one(&state.db)
@@ -433,63 +647,563 @@ pub async fn patch_user_edit(
         user.url.clone()
     };
 
-    if editing_name || editing_url {
+    let editing_profile = !submission.profile.is_empty();
+
+    let mut urls = submission.profile.image_urls();
+    if editing_url {
+        urls.push(&url);
+    }
+
+    match bans::find(&state, user.discord_id as u64, &urls).await {
+        Ok(Some(ban)) => return petring_api_err(StatusCode::FORBIDDEN, &bans::message(&ban)),
+        Ok(None) => {}
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to check bans");
+        }
+    }
+
+    if editing_name || editing_url || editing_profile {
         let mut active_user: UserModel = user.clone().into();
         let now = Utc::now().to_rfc3339();
 
         if editing_name && user.username != username {
-            active_user.username = Set(username.clone());
+            let username = match username::validate(&username) {
+                Ok(username) => username,
+                Err(e) => {
+                    return petring_api_err(
+                        StatusCode::BAD_REQUEST,
+                        &format!("Invalid username: {e}"),
+                    );
+                }
+            };
+            let username_key = username::key(&username);
+
+            // Changing only the case keeps the same key
+            if username_key != user.username_key {
+                match username::taken(&state.db, &username_key, Some(user.id)).await {
+                    Ok(true) => {
+                        return petring_api_err(StatusCode::CONFLICT, "Username already taken");
+                    }
+                    Ok(false) => {}
+                    Err(_) => {
+                        return petring_api_err(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "Failed to fetch user",
+                        );
+                    }
+                }
+            }
+
+            active_user.username = Set(username);
+            active_user.username_key = Set(username_key);
         }
 
+        let policy = match url_policy::load(&state).await {
+            Ok(policy) => policy,
+            Err(_) => {
+                return petring_api_err(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to load url policy",
+                );
+            }
+        };
+
         if editing_url && user.url != url {
-            let url_parts: Vec<&str> = url.split('/').collect();
-            let url_base_url = format!("{}/{}", url_parts[0], url_parts[1]);
-            let url_invalid = patterns
-                .iter()
-                .any(|pattern| url_base_url.contains(pattern));
-
-            if url_invalid {
-                return petring_api_err(StatusCode::BAD_REQUEST, "Invalid url");
+            let canonical = match policy.check(&url, UrlKind::Site).await {
+                Ok(url) => url_policy::canonical(&url),
+                Err(e) => {
+                    return petring_api_err(StatusCode::BAD_REQUEST, &format!("Invalid url: {e}"));
+                }
+            };
+
+            if canonical != user.url_canonical {
+                match Users::find()
+                    .filter(users::Column::UrlCanonical.eq(canonical.clone()))
+                    .one(&state.db)
+                    .await
+                {
+                    Ok(Some(_)) => {
+                        return petring_api_err(StatusCode::CONFLICT, "Site already in the ring");
+                    }
+                    Ok(None) => {}
+                    Err(_) => {
+                        return petring_api_err(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "Failed to fetch user",
+                        );
+                    }
+                }
             }
 
             active_user.url = Set(url.clone());
+            active_user.url_canonical = Set(canonical);
+        }
+
+        if let Err(message) = apply_profile(&policy, &mut active_user, submission.profile).await {
+            return petring_api_err(StatusCode::BAD_REQUEST, &message);
         }
 
         active_user.edited_at = Set(now.clone());
 
-        match active_user.update(&state.db).await {
-            Ok(_) => {
-                info!("Updated user: {username}");
-                return petring_api_response(
-                    StatusCode::OK,
-                    EditUserResponse {
-                        old: UserResponse {
-                            username: user.username.clone(),
-                            url: user.url.clone(),
-                            discord_id: user.discord_id as u64,
-                            verified: user.verified,
-                            created_at: user.created_at.clone(),
-                            edited_at: user.edited_at,
-                            verified_at: user.verified_at.clone(),
-                        },
-                        new: UserResponse {
-                            username,
-                            url,
-                            discord_id: user.discord_id as u64,
-                            verified: true,
-                            created_at: user.created_at,
-                            edited_at: now,
-                            verified_at: user.verified_at,
-                        },
-                    },
+        let txn = match state.db.begin().await {
+            Ok(txn) => txn,
+            Err(_) => {
+                return petring_api_err(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to start transaction",
                 );
             }
+        };
+
+        let updated = match active_user.update(&txn).await {
+            Ok(updated) => updated,
             Err(_) => {
                 error!("Failed to update user: {username}");
                 return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update user");
             }
+        };
+
+        // Widgets out there still link to the old name
+        if updated.username != user.username
+            && username::record_rename(&txn, &user, &updated)
+                .await
+                .is_err()
+        {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to keep the old username",
+            );
         }
+
+        if txn.commit().await.is_err() {
+            error!("Failed to update user: {username}");
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update user");
+        }
+
+        info!("Updated user: {username}");
+        let old = UserResponse::from(&user);
+        let new = UserResponse::from(&updated);
+        audit
+            .record(
+                &state,
+                [user.discord_id as u64],
+                audit::snapshot(&old),
+                audit::snapshot(&new),
+            )
+            .await;
+
+        if updated.url != user.url || updated.username != user.username {
+            backlinks::spawn_verify(state.clone(), updated);
+        }
+
+        return petring_api_response(StatusCode::OK, EditUserResponse { old, new });
     }
 
     petring_api_err(StatusCode::NOT_MODIFIED, "No changes made")
 }
+
+/* Profiles
+ *
+ * Every field is optional, sending an empty value clears it.
+ */
+
+const MAX_DESCRIPTION_LENGTH: usize = 200;
+const MAX_PRONOUNS_LENGTH: usize = 32;
+const MAX_LANGUAGE_LENGTH: usize = 35;
+const MAX_TAGS: usize = 10;
+const MAX_TAG_LENGTH: usize = 24;
+
+impl ProfileFields {
+    fn is_empty(&self) -> bool {
+        self.description.is_none()
+            && self.avatar_url.is_none()
+            && self.button_url.is_none()
+            && self.pronouns.is_none()
+            && self.language.is_none()
+            && self.tags.is_none()
+    }
+
+    fn image_urls(&self) -> Vec<&str> {
+        [&self.avatar_url, &self.button_url]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+fn valid_text(text: &str, max_length: usize) -> bool {
+    text.chars().count() <= max_length && !text.chars().any(char::is_control)
+}
+
+// Empty clears the image
+async fn valid_image_url(policy: &UrlPolicy, url: &str) -> Result<(), String> {
+    if url.is_empty() {
+        return Ok(());
+    }
+
+    policy
+        .check(url, UrlKind::Image)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, &'static str> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase().replace(' ', "-");
+        if tag.is_empty() {
+            continue;
+        }
+
+        if tag.chars().count() > MAX_TAG_LENGTH
+            || !tag.chars().all(|c| c.is_alphanumeric() || c == '-')
+        {
+            return Err("Invalid tag");
+        }
+
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    if normalized.len() > MAX_TAGS {
+        return Err("Too many tags");
+    }
+
+    Ok(normalized)
+}
+
+async fn apply_profile(
+    policy: &UrlPolicy,
+    active_user: &mut UserModel,
+    profile: ProfileFields,
+) -> Result<(), String> {
+    if let Some(description) = profile.description {
+        let description = description.trim();
+        if !valid_text(description, MAX_DESCRIPTION_LENGTH) {
+            return Err("Invalid description".to_string());
+        }
+        active_user.description = Set(description.to_string());
+    }
+
+    if let Some(avatar_url) = profile.avatar_url {
+        let avatar_url = avatar_url.trim();
+        if let Err(e) = valid_image_url(policy, avatar_url).await {
+            return Err(format!("Invalid avatar url: {e}"));
+        }
+        active_user.avatar_url = Set(avatar_url.to_string());
+    }
+
+    if let Some(button_url) = profile.button_url {
+        let button_url = button_url.trim();
+        if let Err(e) = valid_image_url(policy, button_url).await {
+            return Err(format!("Invalid button url: {e}"));
+        }
+        active_user.button_url = Set(button_url.to_string());
+    }
+
+    if let Some(pronouns) = profile.pronouns {
+        let pronouns = pronouns.trim();
+        if !valid_text(pronouns, MAX_PRONOUNS_LENGTH) {
+            return Err("Invalid pronouns".to_string());
+        }
+        active_user.pronouns = Set(pronouns.to_string());
+    }
+
+    // Language tags like "en", "pt-BR" or "zh-Hant"
+    if let Some(language) = profile.language {
+        let language = language.trim();
+        if language.len() > MAX_LANGUAGE_LENGTH
+            || !language
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err("Invalid language".to_string());
+        }
+        active_user.language = Set(language.to_string());
+    }
+
+    if let Some(tags) = profile.tags {
+        active_user.tags = Set(normalize_tags(tags)?.join(","));
+    }
+
+    Ok(())
+}
+
+/* Ring ordering
+ *
+ * Positions are kept dense (1..=n) whenever the order is rewritten,
+ * ties are broken by id so the order is always well defined.
+ */
+
+async fn ring_order<C: ConnectionTrait>(db: &C) -> Result<Vec<users::Model>, DbErr> {
+    Users::find()
+        .order_by_asc(users::Column::Position)
+        .order_by_asc(users::Column::Id)
+        .all(db)
+        .await
+}
+
+async fn apply_ring_order<C: ConnectionTrait>(db: &C, order: &[users::Model]) -> Result<(), DbErr> {
+    for (index, user) in order.iter().enumerate() {
+        let position = index as i32 + 1;
+        if user.position == position {
+            continue;
+        }
+
+        Users::update_many()
+            .col_expr(users::Column::Position, Expr::value(position))
+            .filter(users::Column::Id.eq(user.id))
+            .exec(db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn ring_order_response(order: &[users::Model]) -> RingOrderResponse {
+    RingOrderResponse {
+        users: order
+            .iter()
+            .enumerate()
+            .map(|(index, user)| RingOrderEntry {
+                position: index as i32 + 1,
+                username: user.username.clone(),
+                discord_id: user.discord_id as u64,
+            })
+            .collect(),
+    }
+}
+
+pub async fn get_ring_order(State(state): State<AppState>) -> impl IntoResponse {
+    match ring_order(&state.db).await {
+        Ok(order) => petring_api_response(StatusCode::OK, ring_order_response(&order)),
+        Err(_) => petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch ring order",
+        ),
+    }
+}
+
+pub async fn patch_ring_move(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(move_request): Json<RingMoveRequest>,
+) -> impl IntoResponse {
+    let txn = match state.db.begin().await {
+        Ok(txn) => txn,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start transaction",
+            );
+        }
+    };
+
+    let mut order = match ring_order(&txn).await {
+        Ok(order) => order,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch ring order",
+            );
+        }
+    };
+
+    let before = audit::snapshot(&ring_order_response(&order));
+
+    let index = match order
+        .iter()
+        .position(|user| user.discord_id == move_request.discord_id as i64)
+    {
+        Some(index) => index,
+        None => {
+            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
+        }
+    };
+
+    let user = order.remove(index);
+    let target = move_request.position.clamp(1, order.len() + 1) - 1;
+    order.insert(target, user);
+
+    if apply_ring_order(&txn, &order).await.is_err() {
+        return petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update ring order",
+        );
+    }
+
+    match txn.commit().await {
+        Ok(_) => {
+            let after = ring_order_response(&order);
+            audit
+                .record(
+                    &state,
+                    [move_request.discord_id],
+                    before,
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(_) => petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update ring order",
+        ),
+    }
+}
+
+pub async fn patch_ring_swap(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(swap_request): Json<RingSwapRequest>,
+) -> impl IntoResponse {
+    let txn = match state.db.begin().await {
+        Ok(txn) => txn,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start transaction",
+            );
+        }
+    };
+
+    let mut order = match ring_order(&txn).await {
+        Ok(order) => order,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch ring order",
+            );
+        }
+    };
+
+    let before = audit::snapshot(&ring_order_response(&order));
+
+    let first = order
+        .iter()
+        .position(|user| user.discord_id == swap_request.first_discord_id as i64);
+    let second = order
+        .iter()
+        .position(|user| user.discord_id == swap_request.second_discord_id as i64);
+
+    let (first, second) = match (first, second) {
+        (Some(first), Some(second)) => (first, second),
+        _ => {
+            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
+        }
+    };
+
+    order.swap(first, second);
+
+    if apply_ring_order(&txn, &order).await.is_err() {
+        return petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update ring order",
+        );
+    }
+
+    match txn.commit().await {
+        Ok(_) => {
+            let after = ring_order_response(&order);
+            audit
+                .record(
+                    &state,
+                    [
+                        swap_request.first_discord_id,
+                        swap_request.second_discord_id,
+                    ],
+                    before,
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(_) => petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update ring order",
+        ),
+    }
+}
+
+pub async fn patch_ring_order(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(order_request): Json<RingOrderRequest>,
+) -> impl IntoResponse {
+    let txn = match state.db.begin().await {
+        Ok(txn) => txn,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start transaction",
+            );
+        }
+    };
+
+    let current_order = match ring_order(&txn).await {
+        Ok(order) => order,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch ring order",
+            );
+        }
+    };
+
+    let before = audit::snapshot(&ring_order_response(&current_order));
+
+    let requested: HashSet<u64> = order_request.discord_ids.iter().copied().collect();
+    if requested.len() != order_request.discord_ids.len() {
+        return petring_api_err(StatusCode::BAD_REQUEST, "Duplicate discord ids in order");
+    }
+
+    let current: HashSet<u64> = current_order
+        .iter()
+        .map(|user| user.discord_id as u64)
+        .collect();
+    if requested != current {
+        return petring_api_err(
+            StatusCode::BAD_REQUEST,
+            "Order must contain every member exactly once",
+        );
+    }
+
+    let mut order = Vec::with_capacity(current_order.len());
+    for discord_id in &order_request.discord_ids {
+        if let Some(user) = current_order
+            .iter()
+            .find(|user| user.discord_id == *discord_id as i64)
+        {
+            order.push(user.clone());
+        }
+    }
+
+    if apply_ring_order(&txn, &order).await.is_err() {
+        return petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update ring order",
+        );
+    }
+
+    match txn.commit().await {
+        Ok(_) => {
+            let after = ring_order_response(&order);
+            audit
+                .record(
+                    &state,
+                    order_request.discord_ids.iter().copied(),
+                    before,
+                    audit::snapshot(&after),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(_) => petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update ring order",
+        ),
+    }
+}
@@ -0,0 +1,396 @@
+use super::{
+    RingCreateRequest, RingMembersRequest, RingOrderEntry, RingResponse,
+    audit::{self, Audit},
+    database::{
+        entities::{RingMemberModel, RingMembers, RingModel, Rings, Users},
+        ring_members, rings, users,
+    },
+    petring_api_err, petring_api_response,
+    state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    Set, TransactionTrait,
+};
+use serde_json::Value;
+#[allow(unused_imports)]
+use tracing::{debug, error, info};
+
+/* Sub-rings
+ *
+ * Themed rings that reuse existing members, each with its own ordering.
+ * Members are appended to the end of a ring when they're added.
+ */
+
+// "default" is what the unscoped routes navigate and "order" is taken by
+// `/get/ring/order`, so neither can be used as a slug.
+const RESERVED_SLUGS: [&str; 2] = ["default", "order"];
+
+fn valid_slug(slug: &str) -> bool {
+    (1..=32).contains(&slug.len())
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+        && !RESERVED_SLUGS.contains(&slug)
+}
+
+async fn find_ring<C: ConnectionTrait>(db: &C, slug: &str) -> Result<Option<rings::Model>, DbErr> {
+    Rings::find()
+        .filter(rings::Column::Slug.eq(slug))
+        .one(db)
+        .await
+}
+
+async fn ring_response<C: ConnectionTrait>(
+    db: &C,
+    ring: rings::Model,
+) -> Result<RingResponse, DbErr> {
+    let members = RingMembers::find()
+        .filter(ring_members::Column::RingId.eq(ring.id))
+        .order_by_asc(ring_members::Column::Position)
+        .order_by_asc(ring_members::Column::Id)
+        .find_also_related(Users)
+        .all(db)
+        .await?;
+
+    Ok(RingResponse {
+        slug: ring.slug,
+        name: ring.name,
+        description: ring.description,
+        created_at: ring.created_at,
+        members: members
+            .into_iter()
+            .filter_map(|(member, user)| {
+                user.map(|user| RingOrderEntry {
+                    position: member.position,
+                    username: user.username,
+                    discord_id: user.discord_id as u64,
+                })
+            })
+            .collect(),
+    })
+}
+
+pub async fn get_ring(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> impl IntoResponse {
+    let ring = match find_ring(&state.db, &slug).await {
+        Ok(Some(ring)) => ring,
+        Ok(None) => {
+            return petring_api_err(StatusCode::NOT_FOUND, "Ring not found");
+        }
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    };
+
+    match ring_response(&state.db, ring).await {
+        Ok(response) => petring_api_response(StatusCode::OK, response),
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring"),
+    }
+}
+
+pub async fn post_ring_create(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(creation): Json<RingCreateRequest>,
+) -> impl IntoResponse {
+    if !valid_slug(&creation.slug) {
+        return petring_api_err(StatusCode::BAD_REQUEST, "Invalid slug");
+    }
+
+    if creation.name.trim().is_empty() {
+        return petring_api_err(StatusCode::BAD_REQUEST, "Invalid name");
+    }
+
+    match find_ring(&state.db, &creation.slug).await {
+        Ok(Some(_)) => {
+            return petring_api_err(StatusCode::CONFLICT, "Ring already exists");
+        }
+        Ok(None) => {}
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    }
+
+    let db_ring = RingModel {
+        slug: Set(creation.slug.clone()),
+        name: Set(creation.name.trim().to_string()),
+        description: Set(creation.description.unwrap_or_default()),
+        created_at: Set(Utc::now().to_rfc3339()),
+        ..Default::default()
+    };
+
+    match db_ring.insert(&state.db).await {
+        Ok(ring) => {
+            info!("Created ring: {}", ring.slug);
+            let after = RingResponse {
+                slug: ring.slug,
+                name: ring.name,
+                description: ring.description,
+                created_at: ring.created_at,
+                members: Vec::new(),
+            };
+            audit
+                .record(&state, [], Value::Null, audit::snapshot(&after))
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(e) => petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to insert ring: {e}"),
+        ),
+    }
+}
+
+pub async fn delete_ring(
+    State(state): State<AppState>,
+    audit: Audit,
+    Path(slug): Path<String>,
+) -> impl IntoResponse {
+    let ring = match find_ring(&state.db, &slug).await {
+        Ok(Some(ring)) => ring,
+        Ok(None) => {
+            return petring_api_err(StatusCode::NOT_FOUND, "Ring not found");
+        }
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    };
+
+    let response = match ring_response(&state.db, ring.clone()).await {
+        Ok(response) => response,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    };
+
+    match Rings::delete_by_id(ring.id).exec(&state.db).await {
+        Ok(_) => {
+            info!("Deleted ring: {}", ring.slug);
+            audit
+                .record(
+                    &state,
+                    response.members.iter().map(|member| member.discord_id),
+                    audit::snapshot(&response),
+                    Value::Null,
+                )
+                .await;
+            petring_api_response(StatusCode::OK, response)
+        }
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete ring"),
+    }
+}
+
+pub async fn post_ring_members(
+    State(state): State<AppState>,
+    audit: Audit,
+    Path(slug): Path<String>,
+    Json(members_request): Json<RingMembersRequest>,
+) -> impl IntoResponse {
+    let txn = match state.db.begin().await {
+        Ok(txn) => txn,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to start transaction",
+            );
+        }
+    };
+
+    let ring = match find_ring(&txn, &slug).await {
+        Ok(Some(ring)) => ring,
+        Ok(None) => {
+            return petring_api_err(StatusCode::NOT_FOUND, "Ring not found");
+        }
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    };
+
+    let before = match ring_response(&txn, ring.clone()).await {
+        Ok(response) => audit::snapshot(&response),
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    };
+
+    let mut position = match RingMembers::find()
+        .filter(ring_members::Column::RingId.eq(ring.id))
+        .order_by_desc(ring_members::Column::Position)
+        .one(&txn)
+        .await
+    {
+        Ok(Some(last)) => last.position,
+        Ok(None) => 0,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    };
+
+    let mut discord_ids: Vec<i64> = Vec::new();
+    for discord_id in &members_request.discord_ids {
+        if !discord_ids.contains(&(*discord_id as i64)) {
+            discord_ids.push(*discord_id as i64);
+        }
+    }
+
+    let users = match Users::find()
+        .filter(users::Column::DiscordId.is_in(discord_ids.clone()))
+        .all(&txn)
+        .await
+    {
+        Ok(users) => users,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users");
+        }
+    };
+
+    if users.len() != discord_ids.len() {
+        return petring_api_err(StatusCode::NOT_FOUND, "User not found");
+    }
+
+    let existing: Vec<i32> = match RingMembers::find()
+        .filter(ring_members::Column::RingId.eq(ring.id))
+        .all(&txn)
+        .await
+    {
+        Ok(members) => members.iter().map(|member| member.user_id).collect(),
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    };
+
+    // Keep the order the moderator asked for
+    let now = Utc::now().to_rfc3339();
+    for discord_id in &discord_ids {
+        let user = match users.iter().find(|user| user.discord_id == *discord_id) {
+            Some(user) => user,
+            None => continue,
+        };
+
+        if existing.contains(&user.id) {
+            continue;
+        }
+
+        position += 1;
+        let member = RingMemberModel {
+            ring_id: Set(ring.id),
+            user_id: Set(user.id),
+            position: Set(position),
+            created_at: Set(now.clone()),
+            ..Default::default()
+        };
+
+        if member.insert(&txn).await.is_err() {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to insert ring member",
+            );
+        }
+    }
+
+    let response = match ring_response(&txn, ring).await {
+        Ok(response) => response,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    };
+
+    match txn.commit().await {
+        Ok(_) => {
+            audit
+                .record(
+                    &state,
+                    members_request.discord_ids.iter().copied(),
+                    before,
+                    audit::snapshot(&response),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, response)
+        }
+        Err(_) => petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update ring members",
+        ),
+    }
+}
+
+pub async fn delete_ring_members(
+    State(state): State<AppState>,
+    audit: Audit,
+    Path(slug): Path<String>,
+    Json(members_request): Json<RingMembersRequest>,
+) -> impl IntoResponse {
+    let ring = match find_ring(&state.db, &slug).await {
+        Ok(Some(ring)) => ring,
+        Ok(None) => {
+            return petring_api_err(StatusCode::NOT_FOUND, "Ring not found");
+        }
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    };
+
+    let before = match ring_response(&state.db, ring.clone()).await {
+        Ok(response) => audit::snapshot(&response),
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring");
+        }
+    };
+
+    let discord_ids: Vec<i64> = members_request
+        .discord_ids
+        .iter()
+        .map(|discord_id| *discord_id as i64)
+        .collect();
+
+    let user_ids: Vec<i32> = match Users::find()
+        .filter(users::Column::DiscordId.is_in(discord_ids))
+        .all(&state.db)
+        .await
+    {
+        Ok(users) => users.iter().map(|user| user.id).collect(),
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users");
+        }
+    };
+
+    if RingMembers::delete_many()
+        .filter(ring_members::Column::RingId.eq(ring.id))
+        .filter(ring_members::Column::UserId.is_in(user_ids))
+        .exec(&state.db)
+        .await
+        .is_err()
+    {
+        return petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete ring members",
+        );
+    }
+
+    match ring_response(&state.db, ring).await {
+        Ok(response) => {
+            audit
+                .record(
+                    &state,
+                    members_request.discord_ids.iter().copied(),
+                    before,
+                    audit::snapshot(&response),
+                )
+                .await;
+            petring_api_response(StatusCode::OK, response)
+        }
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ring"),
+    }
+}
@@ -0,0 +1,147 @@
+use super::{
+    UrlCheckQuery, UrlCheckResponse, UrlPolicyResponse, UrlRuleRequest, UrlRuleResponse,
+    audit::{self, Audit},
+    database::{
+        entities::{UrlRuleModel, UrlRules},
+        url_rules,
+    },
+    petring_api_err, petring_api_response,
+    state::AppState,
+};
+use crate::petring::{bans::parse_host, url_policy};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use serde_json::Value;
+
+/* Runtime url rules
+ *
+ * These sit on top of the rules in `petring-api.toml`. Config rules are
+ * listed too so the bot can show the whole policy, but only the ones
+ * stored here can be removed.
+ */
+
+pub async fn get_url_policy(State(state): State<AppState>) -> impl IntoResponse {
+    match url_policy::load(&state).await {
+        Ok(policy) => petring_api_response(
+            StatusCode::OK,
+            UrlPolicyResponse {
+                resolve_hosts: policy.resolve_hosts,
+                rules: policy.rules.iter().map(UrlRuleResponse::from).collect(),
+            },
+        ),
+        Err(_) => petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to load url policy",
+        ),
+    }
+}
+
+pub async fn get_url_check(
+    State(state): State<AppState>,
+    Query(query): Query<UrlCheckQuery>,
+) -> impl IntoResponse {
+    let policy = match url_policy::load(&state).await {
+        Ok(policy) => policy,
+        Err(_) => {
+            return petring_api_err(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load url policy",
+            );
+        }
+    };
+
+    let response = match policy.check(&query.url, query.kind).await {
+        Ok(url) => UrlCheckResponse {
+            url: url.to_string(),
+            allowed: true,
+            reason: None,
+        },
+        Err(e) => UrlCheckResponse {
+            url: query.url,
+            allowed: false,
+            reason: Some(e.to_string()),
+        },
+    };
+
+    petring_api_response(StatusCode::OK, response)
+}
+
+pub async fn post_url_rule(
+    State(state): State<AppState>,
+    audit: Audit,
+    Json(request): Json<UrlRuleRequest>,
+) -> impl IntoResponse {
+    let host = match parse_host(&request.host) {
+        Some(host) => host,
+        None => return petring_api_err(StatusCode::BAD_REQUEST, "Invalid host"),
+    };
+
+    match UrlRules::find()
+        .filter(url_rules::Column::Kind.eq(request.kind))
+        .filter(url_rules::Column::Host.eq(host.clone()))
+        .one(&state.db)
+        .await
+    {
+        Ok(Some(_)) => {
+            return petring_api_err(StatusCode::CONFLICT, "Rule for this host already exists");
+        }
+        Ok(None) => {}
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch rule");
+        }
+    }
+
+    let rule = UrlRuleModel {
+        kind: Set(request.kind),
+        action: Set(request.action),
+        host: Set(host),
+        created_by: Set(audit.actor_id().map(|id| id as i64)),
+        created_at: Set(Utc::now().to_rfc3339()),
+        ..Default::default()
+    };
+
+    match rule.insert(&state.db).await {
+        Ok(rule) => {
+            let after = UrlRuleResponse::from(&rule);
+            audit
+                .record(&state, [], Value::Null, audit::snapshot(&after))
+                .await;
+            petring_api_response(StatusCode::OK, after)
+        }
+        Err(e) => petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to insert rule: {e}"),
+        ),
+    }
+}
+
+pub async fn delete_url_rule(
+    State(state): State<AppState>,
+    audit: Audit,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let rule = match UrlRules::find_by_id(id).one(&state.db).await {
+        Ok(Some(rule)) => rule,
+        Ok(None) => return petring_api_err(StatusCode::NOT_FOUND, "Rule not found"),
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch rule");
+        }
+    };
+
+    match UrlRules::delete_by_id(rule.id).exec(&state.db).await {
+        Ok(_) => {
+            let before = UrlRuleResponse::from(&rule);
+            audit
+                .record(&state, [], audit::snapshot(&before), Value::Null)
+                .await;
+            petring_api_response(StatusCode::OK, before)
+        }
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete rule"),
+    }
+}
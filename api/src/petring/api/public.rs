@@ -1,24 +1,40 @@
 use super::{
-    PetRingResult, PublicAdResponse, Serializeableuser, ServerInfo, UsersResponse,
-    database::{
-        ads,
-        entities::{Ads, Users},
-        users,
-    },
-    petring_api_err, petring_api_response,
+    AdFormatQuery, ButtonResponse, ButtonsResponse, DirectoryUser, NeighborsResponse,
+    PetRingResult, ProfileResponse, PublicAdResponse, RingSummary, RingsResponse, SearchQuery,
+    SearchResponse, SearchResultResponse, Serializeableuser, ServerInfo, UsersResponse,
+    database::{ads, entities::Rings, rings, users},
+    directory::{self, ListOptions},
+    list_error_response, petring_api_err, petring_api_response,
+    ring::{self, Scope},
+    search,
     state::AppState,
 };
-use crate::APP_START;
+use crate::{
+    APP_START, HtmlTemplate,
+    petring::{
+        ad_images,
+        ad_stats::Event,
+        buttons::{self, ButtonImage},
+        rotation,
+    },
+};
+use askama::Template;
 use axum::{
-    extract::{Path, State},
-    http::{HeaderMap, HeaderValue, StatusCode, header::LOCATION},
+    body::Body,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{
+        HeaderMap, HeaderValue, Response, StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, LOCATION, X_CONTENT_TYPE_OPTIONS},
+    },
     response::{Html, IntoResponse},
 };
 use humantime::format_duration;
-use rand::{SeedableRng, rngs::StdRng, seq::IndexedRandom};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
-use serde::Serialize;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use std::{
+    net::SocketAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tokio::{fs::File, io::AsyncReadExt};
 #[allow(unused_imports)]
 use tracing::{debug, error, info};
@@ -29,7 +45,13 @@ pub async fn get_public_api_index() -> impl IntoResponse {
         "/get/uptime",
         "/get/users",
         "/get/users/random",
+        "/get/users/search?q=",
+        "/get/rings",
         "/get/random-ad",
+        "/get/random-ad?format=",
+        "/get/buttons",
+        "/buttons",
+        "/embed/buttons",
     ];
 
     let wrap_endpoints_with_hyperlinks = current_endpoints
@@ -83,7 +105,7 @@ async fn get_system_uptime() -> PetRingResult<Duration> {
     Ok(Duration::from_secs_f64(uptime))
 }
 
-pub async fn get_server_info() -> impl IntoResponse {
+pub async fn get_server_info(State(state): State<AppState>) -> impl IntoResponse {
     let app_uptime = get_app_uptime().await.unwrap_or_else(|e| {
         eprintln!("Error getting app uptime: {}", e);
         Duration::new(0, 0)
@@ -97,11 +119,12 @@ pub async fn get_server_info() -> impl IntoResponse {
     petring_api_response(
         StatusCode::OK,
         ServerInfo {
-            name: "petring".to_string(),
+            name: state.community.name.clone(),
             version: "0.1.0".to_string(),
-            description: "A webring for the Jess Museum Discord server".to_string(),
+            description: state.community.description.clone(),
             source: "https://github.com/h4rldev/petring".to_string(),
-            authors: ["h4rl".to_string(), "doloro".to_string()],
+            authors: state.community.authors.clone(),
+            url: state.community.public_url.clone(),
             license: "BSD 3-Clause License".to_string(),
             server_uptime: format_duration(app_uptime).to_string(),
             system_uptime: format_duration(system_uptime).to_string(),
@@ -135,354 +158,614 @@ pub async fn get_uptime() -> impl IntoResponse {
     )
 }
 
-pub async fn get_all_users(State(state): State<AppState>) -> impl IntoResponse {
-    let users = match Users::find()
-        .filter(users::Column::Verified.eq(true))
-        .all(&state.db)
-        .await
-    {
-        Ok(users) => users,
-        Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users");
+#[derive(Deserialize)]
+pub struct RingPath {
+    pub ring: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RingUserPath {
+    pub ring: Option<String>,
+    pub username: String,
+}
+
+// The ring a request navigates, sub-rings live under `/ring/{ring}/...`
+// and everything else uses the default ring.
+struct RingContext {
+    scope: Scope,
+    name: String,
+}
+
+async fn ring_context(state: &AppState, ring: Option<&str>) -> Result<RingContext, Response<Body>> {
+    let slug = match ring {
+        Some(slug) => slug,
+        None => {
+            return Ok(RingContext {
+                scope: Scope::Default,
+                name: state.community.name.clone(),
+            });
         }
     };
 
+    match ring::find_ring(state, slug).await {
+        Ok(Some(ring)) => Ok(RingContext {
+            scope: Scope::Ring(ring.id),
+            name: ring.name,
+        }),
+        Ok(None) => Err(petring_api_err(StatusCode::NOT_FOUND, "Ring not found")),
+        Err(_) => Err(petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch ring",
+        )),
+    }
+}
+
+pub async fn get_all_users(
+    State(state): State<AppState>,
+    Path(path): Path<RingPath>,
+    Query(options): Query<ListOptions>,
+) -> impl IntoResponse {
+    let context = match ring_context(&state, path.ring.as_deref()).await {
+        Ok(context) => context,
+        Err(response) => return response,
+    };
+
+    let select = ring::members(context.scope).filter(ring::eligible(&state));
+    let page = match directory::list(&state, context.scope, select, &options).await {
+        Ok(page) => page,
+        Err(e) => return list_error_response(e),
+    };
+
     petring_api_response(
         StatusCode::OK,
         UsersResponse {
-            users: users
+            users: page
+                .users
                 .iter()
-                .map(|user| Serializeableuser {
+                .map(|user| DirectoryUser {
                     username: user.username.clone(),
                     url: user.url.clone(),
+                    profile: ProfileResponse::from(user),
                 })
                 .collect(),
+            next_cursor: page.next_cursor,
         },
     )
 }
 
-pub async fn get_random_user(State(state): State<AppState>) -> impl IntoResponse {
-    let users = match Users::find()
-        .filter(users::Column::Verified.eq(true))
+const DEFAULT_SEARCH_RESULTS: u64 = 20;
+
+/* GET /get/users/search?q=
+ *
+ * Searches usernames, descriptions and tags of members in the ring.
+ */
+
+pub async fn get_users_search(
+    State(state): State<AppState>,
+    Path(path): Path<RingPath>,
+    Query(query): Query<SearchQuery>,
+) -> impl IntoResponse {
+    let context = match ring_context(&state, path.ring.as_deref()).await {
+        Ok(context) => context,
+        Err(response) => return response,
+    };
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_RESULTS)
+        .clamp(1, search::MAX_RESULTS);
+
+    let results = match search::search(&state, context.scope, &query.q, limit).await {
+        Ok(results) => results,
+        Err(e) => {
+            error!("Failed to search users: {e}");
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to search users");
+        }
+    };
+
+    petring_api_response(
+        StatusCode::OK,
+        SearchResponse {
+            query: query.q,
+            users: results
+                .into_iter()
+                .map(|result| SearchResultResponse {
+                    user: DirectoryUser {
+                        username: result.user.username.clone(),
+                        url: result.user.url.clone(),
+                        profile: ProfileResponse::from(&result.user),
+                    },
+                    snippet: result.snippet,
+                    rank: result.rank,
+                })
+                .collect(),
+        },
+    )
+}
+
+pub async fn get_rings(State(state): State<AppState>) -> impl IntoResponse {
+    let rings = match Rings::find()
+        .order_by_asc(rings::Column::Slug)
         .all(&state.db)
         .await
     {
-        Ok(users) => users,
+        Ok(rings) => rings,
         Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users");
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch rings");
         }
     };
 
-    let user = match users.choose(&mut rand::rng()) {
-        Some(user) => user,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "Couldn't pick a random user");
-        }
-    };
+    let mut summaries = Vec::with_capacity(rings.len());
+    for ring in rings {
+        let member_count = match ring::eligible_members(&state, Scope::Ring(ring.id))
+            .count(&state.db)
+            .await
+        {
+            Ok(count) => count,
+            Err(_) => {
+                return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users");
+            }
+        };
 
+        summaries.push(RingSummary {
+            slug: ring.slug,
+            name: ring.name,
+            description: ring.description,
+            member_count,
+        });
+    }
+
+    petring_api_response(StatusCode::OK, RingsResponse { rings: summaries })
+}
+
+fn redirect_to(url: &str) -> Response<Body> {
     let mut headermap = HeaderMap::new();
     headermap.insert(
         LOCATION,
-        HeaderValue::from_str(&user.url).expect("Failed to insert header"),
+        HeaderValue::from_str(url).expect("Failed to insert header"),
     );
 
     (StatusCode::TEMPORARY_REDIRECT, headermap).into_response()
 }
 
+pub async fn get_random_user(
+    State(state): State<AppState>,
+    Path(path): Path<RingPath>,
+) -> impl IntoResponse {
+    let context = match ring_context(&state, path.ring.as_deref()).await {
+        Ok(context) => context,
+        Err(response) => return response,
+    };
+
+    match ring::any_random_member(&state, context.scope).await {
+        Ok(Some(user)) => redirect_to(&user.url),
+        Ok(None) => petring_api_err(StatusCode::NOT_FOUND, "Couldn't pick a random user"),
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users"),
+    }
+}
+
+async fn find_member(
+    state: &AppState,
+    path: &RingUserPath,
+) -> Result<(RingContext, users::Model), Response<Body>> {
+    let context = ring_context(state, path.ring.as_deref()).await?;
+
+    match ring::find_member(state, context.scope, &path.username).await {
+        Ok(Some(user)) => Ok((context, user)),
+        Ok(None) => Err(petring_api_err(StatusCode::NOT_FOUND, "User not found")),
+        Err(_) => Err(petring_api_err(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to fetch user",
+        )),
+    }
+}
+
 /* GET /user/{username}
  *
  */
 
 pub async fn get_user(
     State(state): State<AppState>,
-    Path(username): Path<String>,
+    Path(path): Path<RingUserPath>,
 ) -> impl IntoResponse {
-    let user = match Users::find()
-        .filter(users::Column::Username.eq(username))
-        .one(&state.db)
-        .await
-    {
-        Ok(user) => user,
-        Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
-        }
-    };
+    match find_member(&state, &path).await {
+        Ok((_, user)) => redirect_to(&user.url),
+        Err(response) => response,
+    }
+}
 
-    let user = match user {
-        Some(user) => user,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
-        }
+pub async fn get_user_next(
+    State(state): State<AppState>,
+    Path(path): Path<RingUserPath>,
+) -> impl IntoResponse {
+    let (context, user) = match find_member(&state, &path).await {
+        Ok(found) => found,
+        Err(response) => return response,
     };
 
-    let mut headermap = HeaderMap::new();
-    headermap.insert(
-        LOCATION,
-        HeaderValue::from_str(&user.url).expect("Failed to insert header"),
-    );
+    match ring::next_member(&state, context.scope, &user).await {
+        Ok(Some(next_user)) => redirect_to(&next_user.url),
+        Ok(None) => petring_api_err(StatusCode::NOT_FOUND, "User not found"),
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user"),
+    }
+}
 
-    (StatusCode::TEMPORARY_REDIRECT, headermap).into_response()
+pub async fn get_user_prev(
+    State(state): State<AppState>,
+    Path(path): Path<RingUserPath>,
+) -> impl IntoResponse {
+    let (context, user) = match find_member(&state, &path).await {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+
+    match ring::prev_member(&state, context.scope, &user).await {
+        Ok(Some(prev_user)) => redirect_to(&prev_user.url),
+        Ok(None) => petring_api_err(StatusCode::NOT_FOUND, "User not found"),
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user"),
+    }
 }
 
-pub async fn get_user_next(
+pub async fn get_user_random(
     State(state): State<AppState>,
-    Path(username): Path<String>,
+    Path(path): Path<RingUserPath>,
 ) -> impl IntoResponse {
-    let user = match Users::find()
-        .filter(users::Column::Username.eq(username))
-        .one(&state.db)
-        .await
-    {
-        Ok(user) => user,
-        Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
-        }
+    let (context, current_user) = match find_member(&state, &path).await {
+        Ok(found) => found,
+        Err(response) => return response,
     };
 
-    let user = match user {
-        Some(user) => user,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
-        }
+    match ring::random_member(&state, context.scope, &current_user).await {
+        Ok(Some(user)) => redirect_to(&user.url),
+        Ok(None) => petring_api_err(StatusCode::NOT_FOUND, "Couldn't pick a random user"),
+        Err(_) => petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user"),
+    }
+}
+
+pub async fn get_user_neighbors(
+    State(state): State<AppState>,
+    Path(path): Path<RingUserPath>,
+) -> impl IntoResponse {
+    let (context, current_user) = match find_member(&state, &path).await {
+        Ok(found) => found,
+        Err(response) => return response,
     };
 
-    let is_last = match Users::find()
-        .order_by_desc(users::Column::Id)
-        .one(&state.db)
-        .await
-    {
-        Ok(user) => user,
+    let neighbors = match ring::neighbors(&state, context.scope, &current_user).await {
+        Ok(neighbors) => neighbors,
         Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users");
         }
     };
 
-    let is_last = match is_last {
-        Some(user) => user,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
-        }
+    let serializeable = |user: users::Model| Serializeableuser {
+        username: user.username,
+        url: user.url,
     };
 
-    let next_user = if is_last.id != user.id {
-        let _next_user = match Users::find()
-            .order_by_asc(users::Column::Id)
-            .filter(users::Column::Id.gt(user.id))
-            .one(&state.db)
-            .await
-        {
-            Ok(user) => user,
-            Err(_) => {
-                return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
-            }
-        };
+    petring_api_response(
+        StatusCode::OK,
+        NeighborsResponse {
+            username: current_user.username,
+            prev: neighbors.prev.map(serializeable),
+            next: neighbors.next.map(serializeable),
+            random: neighbors.random.map(serializeable),
+        },
+    )
+}
 
-        match _next_user {
-            Some(user) => user,
-            None => {
-                return petring_api_err(StatusCode::NOT_FOUND, "User not found");
-            }
-        }
-    } else {
-        let _next_user = match Users::find()
-            .order_by_asc(users::Column::Id)
-            .one(&state.db)
-            .await
-        {
-            Ok(user) => user,
-            Err(_) => {
-                return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
-            }
-        };
+const WIDGET_JS: &str = include_str!("../../../static/widget.js");
 
-        match _next_user {
-            Some(user) => user,
-            None => {
-                return petring_api_err(StatusCode::NOT_FOUND, "User not found");
-            }
-        }
-    };
+pub async fn get_widget_js() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, "text/javascript; charset=utf-8"),
+            (CACHE_CONTROL, "max-age=86400"),
+        ],
+        WIDGET_JS,
+    )
+}
 
-    let mut headermap = HeaderMap::new();
-    headermap.insert(
-        LOCATION,
-        HeaderValue::from_str(&next_user.url).expect("Failed to insert header"),
-    );
+#[derive(Deserialize)]
+pub struct EmbedQuery {
+    pub style: Option<String>,
+}
 
-    (StatusCode::TEMPORARY_REDIRECT, headermap).into_response()
+// The template only uses relative links, so the same page works under
+// `/ring/{ring}` and community prefixes.
+#[derive(Template)]
+#[template(path = "iframe.html")]
+struct EmbedTemplate {
+    username: String,
+    ring_name: String,
+    member_count: u64,
+    style: String,
 }
 
-pub async fn get_user_prev(
+const EMBED_STYLES: [&str; 3] = ["default", "dark", "minimal"];
+
+/* GET /embed/{username}
+ *
+ * A self-contained prev / random / next widget members can drop into an iframe.
+ */
+
+pub async fn get_embed(
     State(state): State<AppState>,
-    Path(username): Path<String>,
+    Path(path): Path<RingUserPath>,
+    Query(query): Query<EmbedQuery>,
 ) -> impl IntoResponse {
-    let user = match Users::find()
-        .filter(users::Column::Username.eq(username))
-        .filter(users::Column::Verified.eq(true))
-        .one(&state.db)
-        .await
-    {
-        Ok(user) => user,
-        Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
-        }
-    };
-
-    let user = match user {
-        Some(user) => user,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
-        }
+    let (context, user) = match find_member(&state, &path).await {
+        Ok(found) => found,
+        Err(response) => return response,
     };
 
-    let is_first = match Users::find()
-        .order_by_asc(users::Column::Id)
-        .filter(users::Column::Verified.eq(true))
-        .one(&state.db)
+    let member_count = match ring::eligible_members(&state, context.scope)
+        .count(&state.db)
         .await
     {
-        Ok(user) => user,
+        Ok(count) => count,
         Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users");
         }
     };
 
-    let is_first = match is_first {
-        Some(user) => user,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "User not found");
-        }
-    };
+    let style = query
+        .style
+        .filter(|style| EMBED_STYLES.contains(&style.as_str()))
+        .unwrap_or_else(|| EMBED_STYLES[0].to_string());
 
-    let prev_user = if is_first.id != user.id {
-        let _prev_user = match Users::find()
-            .order_by_desc(users::Column::Id)
-            .filter(users::Column::Id.lt(user.id))
-            .filter(users::Column::Verified.eq(true))
-            .one(&state.db)
-            .await
-        {
-            Ok(user) => user,
-            Err(_) => {
-                return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
-            }
-        };
+    let mut response = HtmlTemplate(EmbedTemplate {
+        username: user.username,
+        ring_name: context.name,
+        member_count,
+        style,
+    })
+    .into_response();
 
-        match _prev_user {
-            Some(user) => user,
-            None => {
-                return petring_api_err(StatusCode::NOT_FOUND, "User not found");
-            }
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("max-age=3600"));
+
+    response
+}
+
+pub async fn get_random_ad(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<AdFormatQuery>,
+) -> impl IntoResponse {
+    let mut select = rotation::eligible(&state);
+    if let Some(format) = query.format {
+        select = select.filter(ads::Column::Format.eq(format));
+    }
+
+    let ads = match select.all(&state.db).await {
+        Ok(ads) => ads,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ads");
         }
-    } else {
-        let _prev_user = match Users::find()
-            .order_by_desc(users::Column::Id)
-            .filter(users::Column::Verified.eq(true))
-            .one(&state.db)
-            .await
-        {
-            Ok(user) => user,
-            Err(_) => {
-                return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
-            }
-        };
+    };
 
-        match _prev_user {
-            Some(user) => user,
-            None => {
-                return petring_api_err(StatusCode::NOT_FOUND, "User not found");
-            }
+    let ad = match state.rotation.pick(&ads) {
+        Some(ad) => ad,
+        None => {
+            return petring_api_err(StatusCode::NOT_FOUND, "Couldn't pick a random ad");
         }
     };
 
-    let mut headermap = HeaderMap::new();
-    headermap.insert(
-        LOCATION,
-        HeaderValue::from_str(&prev_user.url).expect("Failed to insert header"),
-    );
+    let viewer = state.ad_stats.viewer(&headers, address);
+    state.ad_stats.record(ad.id, viewer, Event::Impression);
 
-    (StatusCode::TEMPORARY_REDIRECT, headermap).into_response()
+    petring_api_response(
+        StatusCode::OK,
+        PublicAdResponse {
+            id: ad.id,
+            username: ad.username.clone(),
+            format: ad.format,
+            image_url: if ad.image_hash.is_empty() {
+                ad.image_url.clone()
+            } else {
+                ad_images::local_url(&state, &ad.image_hash)
+            },
+            ad_url: ad.ad_url.clone(),
+            click_url: format!(
+                "{}/ad/{}/click",
+                state.community.public_url.trim_end_matches('/'),
+                ad.id
+            ),
+        },
+    )
 }
 
-pub async fn get_user_random(
+/* GET /ad/{id}/click
+ *
+ * Counts the click and sends the visitor on to the ad. Only ads that could
+ * currently be served redirect, so old links don't keep counting.
+ */
+
+pub async fn get_ad_click(
     State(state): State<AppState>,
-    Path(username): Path<String>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
 ) -> impl IntoResponse {
-    let current_user = match Users::find()
-        .filter(users::Column::Username.eq(username))
-        .filter(users::Column::Verified.eq(true))
+    let ad = match rotation::eligible(&state)
+        .filter(ads::Column::Id.eq(id))
         .one(&state.db)
         .await
     {
-        Ok(user) => user,
+        Ok(Some(ad)) => ad,
+        Ok(None) => return petring_api_err(StatusCode::NOT_FOUND, "Ad not found"),
         Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ad");
         }
     };
 
-    let current_user = match current_user {
-        Some(user) => user,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "Couldn't pick a random user");
-        }
-    };
+    let viewer = state.ad_stats.viewer(&headers, address);
+    state.ad_stats.record(ad.id, viewer, Event::Click);
 
-    let user_seed = current_user.id << 16 | current_user.id;
-    let user_seed = user_seed as u64;
-    let mut rng = StdRng::seed_from_u64(user_seed);
+    redirect_to(&ad.ad_url)
+}
 
-    let users = match Users::find()
-        .filter(users::Column::Verified.eq(true))
-        .all(&state.db)
-        .await
-    {
-        Ok(users) => users,
-        Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch user");
-        }
+/* GET /get/buttons
+ *
+ * The button wall as JSON, see `buttons`.
+ */
+
+pub async fn get_buttons(
+    State(state): State<AppState>,
+    Path(path): Path<RingPath>,
+) -> impl IntoResponse {
+    let context = match ring_context(&state, path.ring.as_deref()).await {
+        Ok(context) => context,
+        Err(response) => return response,
     };
 
-    let user = match users.choose(&mut rng) {
-        Some(user) => user,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "Couldn't pick a random user");
+    let wall = match buttons::wall(&state, context.scope).await {
+        Ok(wall) => wall,
+        Err(_) => {
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users");
         }
     };
 
-    let mut headermap = HeaderMap::new();
-    headermap.insert(
-        LOCATION,
-        HeaderValue::from_str(&user.url).expect("Failed to insert header"),
-    );
+    petring_api_response(
+        StatusCode::OK,
+        ButtonsResponse {
+            ring: context.name,
+            buttons: wall
+                .into_iter()
+                .map(|button| ButtonResponse {
+                    image_url: match button.image {
+                        ButtonImage::Stored(hash) => ad_images::local_url(&state, &hash),
+                        ButtonImage::Remote(url) => url,
+                    },
+                    username: button.user.username,
+                    url: button.user.url,
+                })
+                .collect(),
+        },
+    )
+}
 
-    (StatusCode::TEMPORARY_REDIRECT, headermap).into_response()
+struct ButtonView {
+    username: String,
+    url: String,
+    image_url: String,
 }
 
-pub async fn get_random_ad(State(state): State<AppState>) -> impl IntoResponse {
-    let ads = match Ads::find()
-        .filter(ads::Column::Verified.eq(true))
-        .all(&state.db)
-        .await
-    {
-        Ok(ads) => ads,
+#[derive(Template)]
+#[template(path = "buttons.html")]
+struct ButtonsTemplate {
+    ring_name: String,
+    buttons: Vec<ButtonView>,
+    embed: bool,
+}
+
+async fn buttons_page(state: AppState, ring: Option<String>, embed: bool) -> Response<Body> {
+    let context = match ring_context(&state, ring.as_deref()).await {
+        Ok(context) => context,
+        Err(response) => return response,
+    };
+
+    let wall = match buttons::wall(&state, context.scope).await {
+        Ok(wall) => wall,
         Err(_) => {
-            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch ads");
+            return petring_api_err(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch users");
         }
     };
 
-    let ad = match ads.choose(&mut rand::rng()) {
-        Some(ad) => ad,
-        None => {
-            return petring_api_err(StatusCode::NOT_FOUND, "Couldn't pick a random ad");
-        }
+    // Stored images are linked relative to the page like the embed widget
+    // does, so it works under any prefix. `root` leads back to where `/ads`
+    // lives.
+    let depth = ring.is_some() as usize * 2 + embed as usize;
+    let root = "../".repeat(depth);
+
+    let mut response = HtmlTemplate(ButtonsTemplate {
+        ring_name: context.name,
+        buttons: wall
+            .into_iter()
+            .map(|button| ButtonView {
+                image_url: match button.image {
+                    ButtonImage::Stored(hash) => format!("{root}ads/img/{hash}"),
+                    ButtonImage::Remote(url) => url,
+                },
+                username: button.user.username,
+                url: button.user.url,
+            })
+            .collect(),
+        embed,
+    })
+    .into_response();
+
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("max-age=3600"));
+
+    response
+}
+
+/* GET /buttons
+ *
+ * Every member's button on one page.
+ */
+
+pub async fn get_buttons_page(
+    State(state): State<AppState>,
+    Path(path): Path<RingPath>,
+) -> impl IntoResponse {
+    buttons_page(state, path.ring, false).await
+}
+
+/* GET /embed/buttons
+ *
+ * Just the buttons on a transparent background, for iframes on member sites.
+ */
+
+pub async fn get_buttons_embed(
+    State(state): State<AppState>,
+    Path(path): Path<RingPath>,
+) -> impl IntoResponse {
+    buttons_page(state, path.ring, true).await
+}
+
+/* GET /ads/img/{hash}
+ *
+ * Stored ad images. The hash is of the contents, so a file never changes
+ * and can be cached for as long as browsers allow.
+ */
+
+pub async fn get_ad_image(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> impl IntoResponse {
+    if !ad_images::valid_hash(&hash) {
+        return petring_api_err(StatusCode::NOT_FOUND, "Image not found");
+    }
+
+    let bytes = match tokio::fs::read(ad_images::path_of(&state, &hash)).await {
+        Ok(bytes) => bytes,
+        Err(_) => return petring_api_err(StatusCode::NOT_FOUND, "Image not found"),
     };
 
-    petring_api_response(
+    let mime = match ad_images::sniff(&bytes) {
+        Some(mime) => mime,
+        None => return petring_api_err(StatusCode::NOT_FOUND, "Image not found"),
+    };
+
+    (
         StatusCode::OK,
-        PublicAdResponse {
-            username: ad.username.clone(),
-            image_url: ad.image_url.clone(),
-            ad_url: ad.ad_url.clone(),
-        },
+        [
+            (CONTENT_TYPE, mime.to_string()),
+            (
+                CACHE_CONTROL,
+                "public, max-age=31536000, immutable".to_string(),
+            ),
+            (ETAG, format!("\"{hash}\"")),
+            (X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        ],
+        bytes,
     )
+        .into_response()
 }
@@ -0,0 +1,119 @@
+use super::{
+    api::petring_api_err,
+    database::entities::{AuditLog, AuditLogModel},
+    state::AppState,
+};
+use axum::{
+    extract::{FromRequestParts, MatchedPath},
+    http::{StatusCode, request::Parts},
+    response::Response,
+};
+use chrono::Utc;
+use sea_orm::{EntityTrait, Set};
+use serde::Serialize;
+use serde_json::Value;
+use tracing::error;
+
+/* Audit log
+ *
+ * Every protected handler that changes something takes an `Audit` and
+ * records what it touched once the change went through. The bot is the only
+ * one holding a token, so it names the person it's acting for in the
+ * `X-Actor-Id` header.
+ */
+
+pub const ACTOR_HEADER: &str = "x-actor-id";
+
+pub struct Audit {
+    actor_id: Option<u64>,
+    endpoint: String,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Audit {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let actor_id = match parts.headers.get(ACTOR_HEADER) {
+            Some(value) => Some(
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| value.trim().parse::<u64>().ok())
+                    .ok_or_else(|| {
+                        petring_api_err(StatusCode::BAD_REQUEST, "Invalid X-Actor-Id header")
+                    })?,
+            ),
+            None => None,
+        };
+
+        let path = match parts.extensions.get::<MatchedPath>() {
+            Some(path) => path.as_str().to_string(),
+            None => parts.uri.path().to_string(),
+        };
+
+        Ok(Self {
+            actor_id,
+            endpoint: format!("{} {path}", parts.method),
+        })
+    }
+}
+
+// `Value::Null` is stored as no snapshot, for creations and deletions
+pub fn snapshot<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn stored(value: Value) -> Option<Value> {
+    match value {
+        Value::Null => None,
+        value => Some(value),
+    }
+}
+
+impl Audit {
+    pub fn actor_id(&self) -> Option<u64> {
+        self.actor_id
+    }
+
+    // Changes the api makes by itself, without a request behind them
+    pub fn system(task: &str) -> Self {
+        Self {
+            actor_id: None,
+            endpoint: task.to_string(),
+        }
+    }
+
+    // Moderation requests name the moderator themselves, that wins over the header
+    pub fn acting_as(self, actor_id: u64) -> Self {
+        Self {
+            actor_id: Some(actor_id),
+            ..self
+        }
+    }
+
+    // The change already happened by the time this runs, so a failure to
+    // record it is logged rather than turned into an error response.
+    pub async fn record<I: IntoIterator<Item = u64>>(
+        &self,
+        state: &AppState,
+        targets: I,
+        before: Value,
+        after: Value,
+    ) {
+        let targets: Vec<String> = targets.into_iter().map(|id| id.to_string()).collect();
+
+        let entry = AuditLogModel {
+            actor_id: Set(self.actor_id.map(|id| id as i64)),
+            endpoint: Set(self.endpoint.clone()),
+            targets: Set(targets.join(",")),
+            before: Set(stored(before)),
+            after: Set(stored(after)),
+            created_at: Set(Utc::now().to_rfc3339()),
+            ..Default::default()
+        };
+
+        if let Err(e) = AuditLog::insert(entry).exec(&state.db).await {
+            error!("Failed to record {} in the audit log: {e}", self.endpoint);
+        }
+    }
+}
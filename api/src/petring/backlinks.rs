@@ -0,0 +1,334 @@
+use super::{
+    bans::{host_of, normalize_host},
+    database::{
+        entities::{UsernameAliases, Users},
+        username_aliases, users,
+    },
+    state::AppState,
+    username,
+};
+use chrono::Utc;
+use reqwest::Url;
+use scraper::{Html, Selector};
+use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter, prelude::Expr};
+use std::time::Duration;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, warn};
+
+/* Backlink verification
+ *
+ * Fetches a member's site and looks for links back into the ring, so
+ * moderators don't have to dig through the page source by hand.
+ */
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+pub struct BacklinkReport {
+    pub markers: Vec<String>,
+    pub error: String,
+}
+
+impl BacklinkReport {
+    pub fn verified(&self) -> bool {
+        !self.markers.is_empty()
+    }
+}
+
+// Hosts the ring answers on, a link anywhere else doesn't count
+fn ring_hosts(state: &AppState) -> Vec<String> {
+    let community = &state.community;
+    host_of(&community.public_url)
+        .into_iter()
+        .chain(community.hostnames.iter().map(|host| normalize_host(host)))
+        .collect()
+}
+
+fn on_ring(url: &Url, hosts: &[String]) -> bool {
+    url.host_str()
+        .is_some_and(|host| hosts.contains(&normalize_host(host)))
+}
+
+// The path of a link into the ring that's about this member, like
+// `user/alice` or `embed/alice`. `keys` are the member's username keys, old
+// names included since their links still work.
+fn member_path(base: &Url, href: &str, keys: &[String], hosts: &[String]) -> Option<Vec<String>> {
+    let url = base.join(href).ok()?;
+    if !on_ring(&url, hosts) {
+        return None;
+    }
+
+    let segments: Vec<String> = url
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let names_member = segments.windows(2).any(|pair| {
+        (pair[0] == "user" || pair[0] == "embed") && keys.contains(&username::key(&pair[1]))
+    });
+
+    names_member.then_some(segments)
+}
+
+// Which ring links a single url points to, if any.
+fn marker_for_url(base: &Url, href: &str, keys: &[String], hosts: &[String]) -> Option<String> {
+    let segments = member_path(base, href, keys, hosts)?;
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    let is_member = |name: &str| keys.contains(&username::key(name));
+
+    match segments.as_slice() {
+        [.., "user", name, action] if is_member(name) => match *action {
+            "next" | "prev" | "random" => Some(action.to_string()),
+            _ => None,
+        },
+        [.., "embed", name] if is_member(name) => Some("embed".to_string()),
+        _ => None,
+    }
+}
+
+fn find_markers(base: &Url, html: &str, keys: &[String], hosts: &[String]) -> Vec<String> {
+    let document = Html::parse_document(html);
+    let links = Selector::parse("a[href], link[href], area[href]").expect("Invalid selector");
+    let frames = Selector::parse("iframe[src]").expect("Invalid selector");
+    let scripts = Selector::parse("script[src][data-username]").expect("Invalid selector");
+
+    let mut markers = Vec::new();
+    let mut push = |marker: String| {
+        if !markers.contains(&marker) {
+            markers.push(marker);
+        }
+    };
+
+    for element in document.select(&links) {
+        let href = element.value().attr("href").unwrap_or_default();
+        if let Some(marker) = marker_for_url(base, href, keys, hosts) {
+            push(marker);
+        }
+
+        // <a rel="petring"> and friends, for people that roll their own links.
+        // They still have to lead to this member on the ring.
+        let rel = element.value().attr("rel").unwrap_or_default();
+        if rel
+            .split_whitespace()
+            .any(|token| token.eq_ignore_ascii_case("petring"))
+            && member_path(base, href, keys, hosts).is_some()
+        {
+            push("rel".to_string());
+        }
+    }
+
+    for element in document.select(&frames) {
+        let src = element.value().attr("src").unwrap_or_default();
+        if let Some(marker) = marker_for_url(base, src, keys, hosts) {
+            push(marker);
+        }
+    }
+
+    for element in document.select(&scripts) {
+        let src = element.value().attr("src").unwrap_or_default();
+        let name = element.value().attr("data-username").unwrap_or_default();
+        let on_ring = base.join(src).is_ok_and(|src| on_ring(&src, hosts));
+        if on_ring && src.ends_with("/widget.js") && keys.contains(&username::key(name)) {
+            push("widget".to_string());
+        }
+    }
+
+    markers
+}
+
+async fn fetch_page(state: &AppState, url: &Url) -> Result<String, String> {
+    let mut response = state
+        .http
+        .get(url.clone())
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch site: {e}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Site responded with {}", response.status()));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read site: {e}"))?
+    {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_PAGE_SIZE {
+            return Err("Site is too large to check".to_string());
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+pub async fn check(state: &AppState, user: &users::Model) -> BacklinkReport {
+    // Never empty, the config has to name at least one
+    let hosts = ring_hosts(state);
+
+    let url = match Url::parse(&user.url) {
+        Ok(url) => url,
+        Err(e) => {
+            return BacklinkReport {
+                markers: Vec::new(),
+                error: format!("Invalid url: {e}"),
+            };
+        }
+    };
+
+    let aliases = match UsernameAliases::find()
+        .filter(username_aliases::Column::UserId.eq(user.id))
+        .all(&state.db)
+        .await
+    {
+        Ok(aliases) => aliases,
+        Err(e) => {
+            return BacklinkReport {
+                markers: Vec::new(),
+                error: format!("Failed to fetch old usernames: {e}"),
+            };
+        }
+    };
+    let mut keys = vec![user.username_key.clone()];
+    keys.extend(aliases.into_iter().map(|alias| alias.username_key));
+
+    match fetch_page(state, &url).await {
+        Ok(html) => BacklinkReport {
+            markers: find_markers(&url, &html, &keys, &hosts),
+            error: "".to_string(),
+        },
+        Err(error) => BacklinkReport {
+            markers: Vec::new(),
+            error,
+        },
+    }
+}
+
+// Checks the member's site and stores the result on their user record.
+pub async fn verify(state: &AppState, user: &users::Model) -> Result<users::Model, DbErr> {
+    let report = check(state, user).await;
+    let now = Utc::now().to_rfc3339();
+
+    Users::update_many()
+        .col_expr(
+            users::Column::BacklinkVerified,
+            Expr::value(report.verified()),
+        )
+        .col_expr(
+            users::Column::BacklinkMarkers,
+            Expr::value(report.markers.join(",")),
+        )
+        .col_expr(
+            users::Column::BacklinkError,
+            Expr::value(report.error.clone()),
+        )
+        .col_expr(users::Column::BacklinkCheckedAt, Expr::value(now.clone()))
+        .filter(users::Column::Id.eq(user.id))
+        .exec(&state.db)
+        .await?;
+
+    Ok(users::Model {
+        backlink_verified: report.verified(),
+        backlink_markers: report.markers.join(","),
+        backlink_error: report.error,
+        backlink_checked_at: now,
+        ..user.clone()
+    })
+}
+
+pub fn spawn_verify(state: AppState, user: users::Model) {
+    tokio::spawn(async move {
+        match verify(&state, &user).await {
+            Ok(user) => debug!(
+                "Checked backlinks for {}: {}",
+                user.username, user.backlink_verified
+            ),
+            Err(e) => error!("Failed to store backlinks for {}: {e}", user.username),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers(page: &str, html: &str) -> Vec<String> {
+        let base = Url::parse(page).unwrap();
+        let keys = vec!["alice".to_string(), "old_alice".to_string()];
+        let hosts = vec!["ring.test".to_string()];
+        find_markers(&base, html, &keys, &hosts)
+    }
+
+    #[test]
+    fn counts_links_on_the_ring() {
+        let html = r#"
+            <a href="https://ring.test/user/alice/next">next</a>
+            <a href="https://Ring.Test./user/Alice/prev">prev</a>
+            <iframe src="https://ring.test/t/other/embed/alice"></iframe>
+        "#;
+        assert_eq!(
+            markers("https://alice.dev/", html),
+            vec!["next", "prev", "embed"]
+        );
+    }
+
+    #[test]
+    fn ignores_links_off_the_ring() {
+        let html = r#"
+            <a href="https://evil.test/user/alice/next">next</a>
+            <a href="https://ring.test.evil.test/user/alice/prev">prev</a>
+            <a href="https://ring.test/user/bob/next">someone else</a>
+            <a href="https://ring.test/user/alice/edit">not a ring link</a>
+        "#;
+        assert!(markers("https://alice.dev/", html).is_empty());
+    }
+
+    #[test]
+    fn old_names_still_count() {
+        let html = r#"<a href="https://ring.test/user/old_alice/random">random</a>"#;
+        assert_eq!(markers("https://alice.dev/", html), vec!["random"]);
+    }
+
+    #[test]
+    fn rel_needs_a_link_to_the_member() {
+        let html = r#"<a rel="petring" href="https://evil.example">ring</a>"#;
+        assert!(markers("https://alice.dev/", html).is_empty());
+
+        let html = r#"<a rel="me PetRing" href="https://ring.test/user/alice">ring</a>"#;
+        assert_eq!(markers("https://alice.dev/", html), vec!["rel"]);
+
+        let html = r#"<a rel="petring" href="https://ring.test/user/bob">ring</a>"#;
+        assert!(markers("https://alice.dev/", html).is_empty());
+    }
+
+    #[test]
+    fn widget_script_needs_the_ring_and_the_member() {
+        let html = r#"<script src="https://ring.test/widget.js" data-username="Alice"></script>"#;
+        assert_eq!(markers("https://alice.dev/", html), vec!["widget"]);
+
+        let html = r#"<script src="https://ring.test/widget.js" data-username="bob"></script>"#;
+        assert!(markers("https://alice.dev/", html).is_empty());
+
+        let html = r#"<script src="https://cdn.test/widget.js" data-username="alice"></script>"#;
+        assert!(markers("https://alice.dev/", html).is_empty());
+    }
+
+    #[test]
+    fn relative_links_resolve_against_the_page() {
+        // On the member's own site they point at the member's host
+        let html = r#"<a href="/user/alice/next">next</a>
+            <script src="widget.js" data-username="alice"></script>"#;
+        assert!(markers("https://alice.dev/blog/", html).is_empty());
+
+        // Pages hosted on the ring itself can use relative links
+        let html = r#"<a href="../user/alice/next">next</a>"#;
+        assert_eq!(
+            markers("https://ring.test/t/x/index.html", html),
+            vec!["next"]
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use super::{
+    database::{
+        bans::{self, BanKind},
+        entities::Bans,
+    },
+    state::AppState,
+};
+use chrono::Utc;
+use reqwest::Url;
+use sea_orm::{ColumnTrait, Condition, DbErr, EntityTrait, QueryFilter};
+
+/* Bans
+ *
+ * Checked whenever someone tries to get (back) in: submitting or editing a
+ * member and submitting an ad. A host ban covers its subdomains too, so
+ * banning `example.com` also keeps out `www.example.com`.
+ */
+
+pub fn normalize_host(host: &str) -> String {
+    host.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+pub fn host_of(url: &str) -> Option<String> {
+    Url::parse(url.trim()).ok()?.host_str().map(normalize_host)
+}
+
+// Takes a bare hostname (`*.` allowed) or a whole url
+pub fn parse_host(value: &str) -> Option<String> {
+    let host = if value.contains("://") {
+        host_of(value)?
+    } else {
+        normalize_host(value.trim_start_matches("*."))
+    };
+
+    let valid = !host.is_empty()
+        && host.len() <= 253
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        && !host.split('.').any(str::is_empty);
+
+    valid.then_some(host)
+}
+
+// `a.example.com` gives `a.example.com`, `example.com` and `com`
+fn host_and_parents(host: &str) -> Vec<String> {
+    let mut hosts = vec![host.to_string()];
+    let mut rest = host;
+    while let Some((_, parent)) = rest.split_once('.') {
+        hosts.push(parent.to_string());
+        rest = parent;
+    }
+
+    hosts
+}
+
+// Bans without an expiry never run out
+pub fn active() -> Condition {
+    Condition::any()
+        .add(bans::Column::ExpiresAt.eq(""))
+        .add(bans::Column::ExpiresAt.gt(Utc::now().to_rfc3339()))
+}
+
+pub async fn find(
+    state: &AppState,
+    discord_id: u64,
+    urls: &[&str],
+) -> Result<Option<bans::Model>, DbErr> {
+    let hosts: Vec<String> = urls
+        .iter()
+        .filter_map(|url| host_of(url))
+        .flat_map(|host| host_and_parents(&host))
+        .collect();
+
+    let mut banned = Condition::any().add(
+        Condition::all()
+            .add(bans::Column::Kind.eq(BanKind::DiscordId))
+            .add(bans::Column::Value.eq(discord_id.to_string())),
+    );
+
+    if !hosts.is_empty() {
+        banned = banned.add(
+            Condition::all()
+                .add(bans::Column::Kind.eq(BanKind::Host))
+                .add(bans::Column::Value.is_in(hosts)),
+        );
+    }
+
+    Bans::find()
+        .filter(banned)
+        .filter(active())
+        .one(&state.db)
+        .await
+}
+
+pub fn message(ban: &bans::Model) -> String {
+    if ban.reason.is_empty() {
+        "Banned".to_string()
+    } else {
+        format!("Banned: {}", ban.reason)
+    }
+}
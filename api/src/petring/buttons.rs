@@ -0,0 +1,54 @@
+use super::{
+    database::{AdFormat, ads, users},
+    ring::{self, Scope},
+    rotation,
+    state::AppState,
+};
+use sea_orm::{ColumnTrait, DbErr, QueryFilter};
+use std::collections::HashMap;
+
+/* Button wall
+ *
+ * Every member the ring would send visitors to, in ring order, shown by
+ * their 88x31 button. A running button ad wins since we host a copy of
+ * it, otherwise the button from their profile is used. Members with neither
+ * are left out.
+ */
+
+pub enum ButtonImage {
+    // Served from `/ads/img/{hash}`
+    Stored(String),
+    Remote(String),
+}
+
+pub struct Button {
+    pub user: users::Model,
+    pub image: ButtonImage,
+}
+
+pub async fn wall(state: &AppState, scope: Scope) -> Result<Vec<Button>, DbErr> {
+    let members = ring::eligible_members(state, scope).all(&state.db).await?;
+
+    let mut ads: HashMap<i64, ads::Model> = rotation::eligible(state)
+        .filter(ads::Column::Format.eq(AdFormat::Button))
+        .filter(ads::Column::DiscordId.is_in(members.iter().map(|user| user.discord_id)))
+        .all(&state.db)
+        .await?
+        .into_iter()
+        .map(|ad| (ad.discord_id, ad))
+        .collect();
+
+    Ok(members
+        .into_iter()
+        .filter_map(|user| {
+            let image = match ads.remove(&user.discord_id) {
+                Some(ad) if !ad.image_hash.is_empty() => ButtonImage::Stored(ad.image_hash),
+                Some(ad) => ButtonImage::Remote(ad.image_url),
+                None if !user.button_url.is_empty() => ButtonImage::Remote(user.button_url.clone()),
+                None => return None,
+            };
+
+            Some(Button { user, image })
+        })
+        .collect())
+}
@@ -0,0 +1,81 @@
+use axum::{Router, extract::Request, http::header::HOST, response::Response};
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use tower::ServiceExt;
+
+use super::config::CommunityConfig;
+
+/* Community routing
+ *
+ * Every community gets its own router (and with it its own database, bot
+ * and branding). Requests are matched on the `Host` header first, anything
+ * else goes to the default community, which also serves the others under
+ * `/t/{slug}`. Hostnames of the default community don't need an entry since
+ * they fall through to it anyway.
+ */
+
+#[derive(Clone)]
+pub struct Communities {
+    default: Router,
+    by_host: Arc<HashMap<String, Router>>,
+}
+
+fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    let host = match host.strip_prefix('[') {
+        // IPv6 literals keep their brackets, the port goes
+        Some(rest) => &host[..rest.find(']').map_or(host.len(), |end| end + 2)],
+        None => host.split(':').next().unwrap_or_default(),
+    };
+
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+impl Communities {
+    pub fn new(default: (CommunityConfig, Router), others: Vec<(CommunityConfig, Router)>) -> Self {
+        let (default_config, mut default_router) = default;
+        let mut slugs = vec![default_config.slug.clone()];
+        let mut hostnames: Vec<String> = default_config
+            .hostnames
+            .iter()
+            .map(|hostname| normalize_host(hostname))
+            .collect();
+        let mut by_host = HashMap::new();
+
+        for (config, router) in others {
+            if slugs.contains(&config.slug) {
+                panic!("Duplicate community slug: {}", config.slug);
+            }
+            slugs.push(config.slug.clone());
+
+            for hostname in &config.hostnames {
+                let hostname = normalize_host(hostname);
+                if hostnames.contains(&hostname) {
+                    panic!("Hostname {hostname} is used by more than one community");
+                }
+                hostnames.push(hostname.clone());
+                by_host.insert(hostname, router.clone());
+            }
+
+            default_router = default_router.nest(&format!("/t/{}", config.slug), router);
+        }
+
+        Self {
+            default: default_router,
+            by_host: Arc::new(by_host),
+        }
+    }
+
+    fn router_for(&self, request: &Request) -> Router {
+        request
+            .headers()
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| self.by_host.get(&normalize_host(host)))
+            .unwrap_or(&self.default)
+            .clone()
+    }
+
+    pub async fn dispatch(self, request: Request) -> Result<Response, Infallible> {
+        self.router_for(&request).oneshot(request).await
+    }
+}
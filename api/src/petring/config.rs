@@ -20,6 +20,22 @@ pub struct Config {
     pub tls: TlsConfig,
     pub network: NetworkConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub url_policy: UrlPolicyConfig,
+    #[serde(default)]
+    pub ad_images: AdImagesConfig,
+    #[serde(default)]
+    pub ad_rotation: AdRotationConfig,
+    #[serde(default)]
+    pub ad_approval: AdApprovalConfig,
+    #[serde(default)]
+    pub community: CommunityConfig,
+    // Extra communities hosted by the same process, each with their own
+    // database and bot, reachable by hostname or under `/t/{slug}`.
+    #[serde(default)]
+    pub communities: Vec<CommunityConfig>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -34,6 +50,9 @@ pub struct TlsConfig {
 pub struct NetworkConfig {
     pub ip: String,
     pub port: u16,
+    // Only trust X-Forwarded-For when a proxy in front of us sets it
+    #[serde(default)]
+    pub behind_proxy: bool,
     // Add quic support when quic is implemented
 }
 
@@ -42,6 +61,182 @@ pub struct LoggingConfig {
     pub level: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HealthConfig {
+    pub enable: bool,
+    pub interval_secs: u64,
+    pub timeout_secs: u64,
+    // Members that have been down for longer than this are skipped by the ring
+    pub offline_after_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            interval_secs: 60 * 15,
+            timeout_secs: 10,
+            offline_after_secs: 60 * 60 * 24,
+        }
+    }
+}
+
+// Hosts match themselves and all their subdomains. Allow rules win over
+// deny rules, more of both can be added at runtime and live in the database.
+// Fields left out keep their defaults, so setting `deny_sites` replaces the
+// built in list.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct UrlPolicyConfig {
+    // Look hosts up and refuse ones that point at private addresses
+    pub resolve_hosts: bool,
+    pub allow_sites: Vec<String>,
+    pub deny_sites: Vec<String>,
+    pub allow_images: Vec<String>,
+    pub deny_images: Vec<String>,
+}
+
+fn hosts(hosts: &[&str]) -> Vec<String> {
+    hosts.iter().map(|host| host.to_string()).collect()
+}
+
+impl Default for UrlPolicyConfig {
+    fn default() -> Self {
+        Self {
+            resolve_hosts: true,
+            allow_sites: Vec::new(),
+            deny_sites: hosts(&[
+                "discord.com",
+                "discord.gg",
+                "google.com",
+                "twitter.com",
+                "x.com",
+                "reddit.com",
+                "pixiv.net",
+                "tumblr.com",
+                "facebook.com",
+                "instagram.com",
+                "youtube.com",
+                "youtu.be",
+                "tiktok.com",
+                "snapchat.com",
+                "pinterest.com",
+                "github.com",
+                "gitlab.com",
+                "bitbucket.org",
+                "medium.com",
+                "linkedin.com",
+                "stackoverflow.com",
+                "stackexchange.com",
+            ]),
+            allow_images: Vec::new(),
+            // Hosts that block hotlinking or aren't something a member controls
+            deny_images: hosts(&[
+                "discord.com",
+                "discordapp.com",
+                "discordapp.net",
+                "catbox.moe",
+                "fileditch.com",
+                "fileditchfiles.me",
+                "imageshack.com",
+                "imageshack.us",
+                "google.com",
+                "googleusercontent.com",
+                "ibb.co",
+                "imgbb.com",
+                "gyazo.com",
+                "twitter.com",
+                "twimg.com",
+                "x.com",
+                "reddit.com",
+                "redd.it",
+                "redditmedia.com",
+                "pixiv.net",
+                "pximg.net",
+                "tumblr.com",
+            ]),
+        }
+    }
+}
+
+// Ad images are downloaded once and served from here. Files are named by
+// their hash, so communities can share the directory.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct AdImagesConfig {
+    pub dir: PathBuf,
+    pub max_bytes: usize,
+    pub fetch_timeout_secs: u64,
+    pub max_width: u32,
+    pub max_height: u32,
+}
+
+impl Default for AdImagesConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("ad-images"),
+            max_bytes: 512 * 1024,
+            fetch_timeout_secs: 10,
+            max_width: 1024,
+            max_height: 1024,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct AdRotationConfig {
+    // How often served impressions are written to the database
+    pub flush_interval_secs: u64,
+}
+
+impl Default for AdRotationConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval_secs: 30,
+        }
+    }
+}
+
+// Approved ads go back to pending this long after they were approved, so
+// someone has to look at them again. 0 keeps them approved forever.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct AdApprovalConfig {
+    // 0 keeps approvals forever. Ads approved before this was turned on
+    // count from their `verified_at`, so they can expire right away.
+    pub lifetime_days: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CommunityConfig {
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    pub authors: Vec<String>,
+    pub public_url: String,
+    #[serde(default)]
+    pub hostnames: Vec<String>,
+    // Secrets stay in the environment, these name the variables to read
+    pub database_url_env: String,
+    pub bot_token_env: String,
+}
+
+impl Default for CommunityConfig {
+    fn default() -> Self {
+        Self {
+            slug: "petring".to_string(),
+            name: "petring".to_string(),
+            description: "A webring for the Jess Museum Discord server".to_string(),
+            authors: vec!["h4rl".to_string(), "doloro".to_string()],
+            public_url: "".to_string(),
+            hostnames: Vec::new(),
+            database_url_env: "DATABASE_URL".to_string(),
+            bot_token_env: "BOT_TOKEN".to_string(),
+        }
+    }
+}
+
 impl fmt::Display for Level {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
@@ -81,6 +276,7 @@ impl Config {
                 if e.kind() == std::io::ErrorKind::NotFound {
                     let config = Config::default();
                     config.write()?;
+                    config.validate()?;
                     return Ok(config);
                 }
                 return Err(e.into());
@@ -88,10 +284,38 @@ impl Config {
         };
 
         let config: Config = toml::from_str(&config)?;
+        config.validate()?;
 
         Ok(config)
     }
 
+    // Settings that parse fine but would make a background task misbehave
+    fn validate(&self) -> PetRingResult<()> {
+        let health = &self.health;
+        if health.enable {
+            if health.interval_secs < 60 {
+                return Err("health.interval_secs must be at least 60".into());
+            }
+
+            if health.timeout_secs == 0 || health.timeout_secs >= health.interval_secs {
+                return Err(
+                    "health.timeout_secs must be above 0 and below health.interval_secs".into(),
+                );
+            }
+        }
+
+        // Ad image and click urls are built from public_url, a relative one
+        // would miss the host (and the `/t/{slug}` prefix of the other
+        // communities). Backlinks are checked against its host as well
+        for community in std::iter::once(&self.community).chain(&self.communities) {
+            if community.public_url.trim().is_empty() {
+                return Err(format!("community {} needs a public_url", community.slug).into());
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn tls(&self) -> &TlsConfig {
         &self.tls
     }
@@ -104,6 +328,34 @@ impl Config {
         &self.logging
     }
 
+    pub fn health(&self) -> &HealthConfig {
+        &self.health
+    }
+
+    pub fn url_policy(&self) -> &UrlPolicyConfig {
+        &self.url_policy
+    }
+
+    pub fn ad_images(&self) -> &AdImagesConfig {
+        &self.ad_images
+    }
+
+    pub fn ad_rotation(&self) -> &AdRotationConfig {
+        &self.ad_rotation
+    }
+
+    pub fn ad_approval(&self) -> &AdApprovalConfig {
+        &self.ad_approval
+    }
+
+    pub fn community(&self) -> &CommunityConfig {
+        &self.community
+    }
+
+    pub fn communities(&self) -> &[CommunityConfig] {
+        &self.communities
+    }
+
     pub fn default() -> Self {
         Self {
             tls: TlsConfig {
@@ -115,11 +367,19 @@ impl Config {
             network: NetworkConfig {
                 ip: "0.0.0.0".to_string(),
                 port: 8081,
+                behind_proxy: false,
                 // quic_port: None,
             },
             logging: LoggingConfig {
                 level: "INFO".to_string(),
             },
+            health: HealthConfig::default(),
+            url_policy: UrlPolicyConfig::default(),
+            ad_images: AdImagesConfig::default(),
+            ad_rotation: AdRotationConfig::default(),
+            ad_approval: AdApprovalConfig::default(),
+            community: CommunityConfig::default(),
+            communities: Vec::new(),
         }
     }
 
@@ -1,12 +1,38 @@
+pub mod ad_daily_stats;
 pub mod ads;
+pub mod audit_log;
+pub mod bans;
+pub mod link_health;
+pub mod ring_members;
+pub mod rings;
+pub mod status;
+pub mod url_rules;
+pub mod username_aliases;
 pub mod users;
 
 // Re-export entities for easier access
-pub use ads::{ActiveModel as AdModel, Entity as Ads};
+pub use ad_daily_stats::{ActiveModel as AdDailyStatModel, Entity as AdDailyStats};
+pub use ads::{ActiveModel as AdModel, AdFormat, Entity as Ads};
+pub use audit_log::{ActiveModel as AuditLogModel, Entity as AuditLog};
+pub use bans::{ActiveModel as BanModel, BanKind, Entity as Bans};
+pub use link_health::{ActiveModel as LinkHealthModel, Entity as LinkHealth};
+pub use ring_members::{ActiveModel as RingMemberModel, Entity as RingMembers};
+pub use rings::{ActiveModel as RingModel, Entity as Rings};
+pub use status::Status;
+pub use url_rules::{ActiveModel as UrlRuleModel, Entity as UrlRules, RuleAction, UrlKind};
+pub use username_aliases::{ActiveModel as UsernameAliasModel, Entity as UsernameAliases};
 pub use users::{ActiveModel as UserModel, Entity as Users};
 
 // Entity collection for convenience
 pub mod entities {
+    pub use super::{AdDailyStatModel, AdDailyStats};
     pub use super::{AdModel, Ads};
+    pub use super::{AuditLog, AuditLogModel};
+    pub use super::{BanModel, Bans};
+    pub use super::{LinkHealth, LinkHealthModel};
+    pub use super::{RingMemberModel, RingMembers};
+    pub use super::{RingModel, Rings};
+    pub use super::{UrlRuleModel, UrlRules};
     pub use super::{UserModel, Users};
+    pub use super::{UsernameAliasModel, UsernameAliases};
 }
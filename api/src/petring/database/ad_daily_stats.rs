@@ -0,0 +1,35 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "ad_daily_stats")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub ad_id: i32,
+    // UTC, YYYY-MM-DD
+    pub day: String,
+    #[sea_orm(default_value = "0")]
+    pub impressions: i64,
+    #[sea_orm(default_value = "0")]
+    pub clicks: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::ads::Entity",
+        from = "Column::AdId",
+        to = "super::ads::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Ads,
+}
+
+impl Related<super::ads::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Ads.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
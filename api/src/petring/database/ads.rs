@@ -1,21 +1,96 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use super::status::Status;
+
+// Where on a member's site the ad goes. Each member can have one of each.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum AdFormat {
+    // 88x31, the classic web button
+    #[default]
+    #[sea_orm(string_value = "button")]
+    Button,
+    // 468x60
+    #[sea_orm(string_value = "banner")]
+    Banner,
+    // Any size, as long as it's as wide as it's tall
+    #[sea_orm(string_value = "square")]
+    Square,
+}
+
+impl AdFormat {
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        match self {
+            Self::Button => (width, height) == (88, 31),
+            Self::Banner => (width, height) == (468, 60),
+            Self::Square => width > 0 && width == height,
+        }
+    }
+
+    pub fn requirement(&self) -> &'static str {
+        match self {
+            Self::Button => "button ads have to be 88x31",
+            Self::Banner => "banner ads have to be 468x60",
+            Self::Square => "square ads have to be as wide as they are tall",
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "ads")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = true)]
     pub id: i32,
-    #[sea_orm(unique)]
     pub username: String,
-    #[sea_orm(unique)]
+    // Unique together with `format`
     pub discord_id: i64,
+    #[sea_orm(default_value = "button")]
+    pub format: AdFormat,
     #[sea_orm(unique)]
     pub image_url: String,
-    #[sea_orm(unique)]
+    #[sea_orm(unique, default_value = "")]
+    pub image_url_canonical: String,
+    // Stored copy of the image, see `ad_images`. Empty for ads from before
+    // images were downloaded.
+    #[sea_orm(default_value = "")]
+    pub image_hash: String,
+    #[sea_orm(default_value = "0")]
+    pub image_width: i32,
+    #[sea_orm(default_value = "0")]
+    pub image_height: i32,
+    // Relative share of impressions, see `rotation`
+    #[sea_orm(default_value = "1")]
+    pub weight: i32,
+    // Only what has been flushed so far
+    #[sea_orm(default_value = "0")]
+    pub impressions: i64,
+    // When the ad runs, RFC 3339 in UTC. Empty means no limit on that side.
+    #[sea_orm(default_value = "")]
+    pub starts_at: String,
+    #[sea_orm(default_value = "")]
+    pub ends_at: String,
     pub ad_url: String,
-    #[sea_orm(default_value = "false")]
-    pub verified: bool,
+    #[sea_orm(default_value = "pending")]
+    pub status: Status,
+    #[sea_orm(default_value = "")]
+    pub status_reason: String,
+    pub moderated_by: Option<i64>,
+    #[sea_orm(default_value = "")]
+    pub moderated_at: String,
     pub created_at: String,
     #[sea_orm(default_value = "")]
     pub edited_at: String,
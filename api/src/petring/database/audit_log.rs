@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "audit_log")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    // Discord ID of whoever the bot acted for, none when it acted on its own
+    pub actor_id: Option<i64>,
+    pub endpoint: String,
+    // Comma separated Discord IDs
+    #[sea_orm(default_value = "")]
+    pub targets: String,
+    pub before: Option<Json>,
+    pub after: Option<Json>,
+    pub created_at: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,37 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIter, DeriveActiveEnum, Serialize, Deserialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+#[serde(rename_all = "snake_case")]
+pub enum BanKind {
+    #[sea_orm(string_value = "discord_id")]
+    DiscordId,
+    // Also covers every subdomain of the host
+    #[sea_orm(string_value = "host")]
+    Host,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "bans")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub kind: BanKind,
+    // A Discord ID or a lowercase hostname, depending on `kind`
+    pub value: String,
+    #[sea_orm(default_value = "")]
+    pub reason: String,
+    // Empty for bans that never expire
+    #[sea_orm(default_value = "")]
+    pub expires_at: String,
+    pub created_by: Option<i64>,
+    pub created_at: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,40 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "link_health")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    #[sea_orm(unique)]
+    pub user_id: i32,
+    pub status_code: Option<i32>,
+    pub latency_ms: Option<i64>,
+    #[sea_orm(default_value = "")]
+    pub error: String,
+    #[sea_orm(default_value = "")]
+    pub last_checked_at: String,
+    #[sea_orm(default_value = "")]
+    pub last_up_at: String,
+    #[sea_orm(default_value = "")]
+    pub down_since: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
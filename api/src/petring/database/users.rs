@@ -1,6 +1,8 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use super::status::Status;
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "users")]
 pub struct Model {
@@ -8,17 +10,53 @@ pub struct Model {
     pub id: i32,
     #[sea_orm(unique)]
     pub username: String,
+    // Lowercased, see `username::key`
+    #[sea_orm(unique, default_value = "")]
+    pub username_key: String,
     #[sea_orm(unique)]
     pub discord_id: i64,
     #[sea_orm(unique)]
     pub url: String,
-    #[sea_orm(default_value = "false")]
-    pub verified: bool,
+    // What `url` is compared by, see `url_policy::canonical`
+    #[sea_orm(unique, default_value = "")]
+    pub url_canonical: String,
+    #[sea_orm(default_value = "pending")]
+    pub status: Status,
+    #[sea_orm(default_value = "")]
+    pub status_reason: String,
+    pub moderated_by: Option<i64>,
+    #[sea_orm(default_value = "")]
+    pub moderated_at: String,
     pub created_at: String,
     #[sea_orm(default_value = "")]
     pub edited_at: String,
     #[sea_orm(default_value = "", not_null)]
     pub verified_at: String,
+    #[sea_orm(default_value = "0")]
+    pub position: i32,
+    #[sea_orm(default_value = "false")]
+    pub hidden: bool,
+    #[sea_orm(default_value = "false")]
+    pub backlink_verified: bool,
+    #[sea_orm(default_value = "")]
+    pub backlink_markers: String,
+    #[sea_orm(default_value = "")]
+    pub backlink_error: String,
+    #[sea_orm(default_value = "")]
+    pub backlink_checked_at: String,
+    #[sea_orm(default_value = "")]
+    pub description: String,
+    #[sea_orm(default_value = "")]
+    pub avatar_url: String,
+    #[sea_orm(default_value = "")]
+    pub button_url: String,
+    #[sea_orm(default_value = "")]
+    pub pronouns: String,
+    #[sea_orm(default_value = "")]
+    pub language: String,
+    // Comma separated, lowercase
+    #[sea_orm(default_value = "")]
+    pub tags: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
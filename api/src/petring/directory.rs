@@ -0,0 +1,396 @@
+use super::{
+    database::{
+        Status,
+        entities::{LinkHealth, Users},
+        link_health, users,
+    },
+    health,
+    ring::{self, Scope},
+    state::AppState,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use sea_orm::{
+    ColumnTrait, Condition, DbErr, EntityTrait, Order, QueryFilter, QueryOrder, QuerySelect,
+    QueryTrait, Select, Value,
+    sea_query::{Expr, SimpleExpr},
+};
+use serde::{Deserialize, Serialize};
+
+/* Member listings
+ *
+ * Keyset pagination over users, the cursor holds the sort key and id of the
+ * last member on a page so pages stay stable while members join or leave.
+ */
+
+pub const DEFAULT_PAGE_SIZE: u64 = 50;
+pub const MAX_PAGE_SIZE: u64 = 200;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Position,
+    Joined,
+    Name,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthFilter {
+    Up,
+    Down,
+    Offline,
+    Unchecked,
+}
+
+#[derive(Deserialize)]
+pub struct ListOptions {
+    pub cursor: Option<String>,
+    pub limit: Option<u64>,
+    #[serde(default)]
+    pub sort: SortBy,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub tag: Option<String>,
+    pub health: Option<HealthFilter>,
+    // Only honoured by the protected listing, public ones are always approved
+    pub status: Option<Status>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: SortBy,
+    key: String,
+    id: i32,
+}
+
+pub struct Page {
+    pub users: Vec<users::Model>,
+    pub next_cursor: Option<String>,
+}
+
+pub enum ListError {
+    InvalidCursor,
+    InvalidLimit,
+    InvalidTag,
+    Db(DbErr),
+}
+
+impl From<DbErr> for ListError {
+    fn from(e: DbErr) -> Self {
+        Self::Db(e)
+    }
+}
+
+fn sort_expr(scope: Scope, sort: SortBy) -> SimpleExpr {
+    match sort {
+        SortBy::Position => ring::position(scope),
+        SortBy::Joined => Expr::col((Users, users::Column::CreatedAt)).into(),
+        SortBy::Name => Expr::col((Users, users::Column::Username)).into(),
+    }
+}
+
+fn key_value(sort: SortBy, key: &str) -> Result<Value, ListError> {
+    match sort {
+        SortBy::Position => key
+            .parse::<i32>()
+            .map(Value::from)
+            .map_err(|_| ListError::InvalidCursor),
+        SortBy::Joined | SortBy::Name => Ok(Value::from(key)),
+    }
+}
+
+async fn key_of(
+    state: &AppState,
+    scope: Scope,
+    sort: SortBy,
+    user: &users::Model,
+) -> Result<String, DbErr> {
+    match sort {
+        SortBy::Position => Ok(ring::position_of(state, scope, user).await?.to_string()),
+        SortBy::Joined => Ok(user.created_at.clone()),
+        SortBy::Name => Ok(user.username.clone()),
+    }
+}
+
+fn decode_cursor(cursor: &str) -> Result<Cursor, ListError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|_| ListError::InvalidCursor)?;
+    serde_json::from_slice(&bytes).map_err(|_| ListError::InvalidCursor)
+}
+
+fn encode_cursor(cursor: &Cursor) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).expect("Failed to serialize cursor"))
+}
+
+fn health_filter(state: &AppState, filter: HealthFilter) -> SimpleExpr {
+    let checked = LinkHealth::find()
+        .select_only()
+        .column(link_health::Column::UserId);
+
+    match filter {
+        HealthFilter::Up => users::Column::Id.in_subquery(
+            checked
+                .filter(link_health::Column::DownSince.eq(""))
+                .into_query(),
+        ),
+        HealthFilter::Down => users::Column::Id.in_subquery(
+            checked
+                .filter(link_health::Column::DownSince.ne(""))
+                .into_query(),
+        ),
+        HealthFilter::Offline => users::Column::Id.in_subquery(
+            checked
+                .filter(link_health::Column::DownSince.ne(""))
+                .filter(link_health::Column::DownSince.lt(health::offline_cutoff(state)))
+                .into_query(),
+        ),
+        HealthFilter::Unchecked => users::Column::Id.not_in_subquery(checked.into_query()),
+    }
+}
+
+// Tags are stored comma separated, so pad both ends to match whole tags only.
+// Only what a profile tag can hold gets through, which keeps `%` and `_`
+// out of the pattern.
+fn tag_filter(tag: &str) -> Result<SimpleExpr, ListError> {
+    let tag = tag.trim().to_lowercase().replace(' ', "-");
+    if tag.is_empty() || !tag.chars().all(|c| c.is_alphanumeric() || c == '-') {
+        return Err(ListError::InvalidTag);
+    }
+
+    Ok(Expr::cust_with_values(
+        r#"(',' || "users"."tags" || ',') LIKE ?"#,
+        [format!("%,{tag},%")],
+    ))
+}
+
+pub async fn list(
+    state: &AppState,
+    scope: Scope,
+    select: Select<Users>,
+    options: &ListOptions,
+) -> Result<Page, ListError> {
+    let limit = match options.limit {
+        Some(0) => return Err(ListError::InvalidLimit),
+        Some(limit) => limit.min(MAX_PAGE_SIZE),
+        None => DEFAULT_PAGE_SIZE,
+    };
+
+    let sort = sort_expr(scope, options.sort);
+    let order = match options.order {
+        SortOrder::Asc => Order::Asc,
+        SortOrder::Desc => Order::Desc,
+    };
+
+    let mut select = select;
+
+    if let Some(tag) = &options.tag {
+        select = select.filter(tag_filter(tag)?);
+    }
+
+    if let Some(filter) = options.health {
+        select = select.filter(health_filter(state, filter));
+    }
+
+    if let Some(cursor) = &options.cursor {
+        let cursor = decode_cursor(cursor)?;
+        if cursor.sort != options.sort {
+            return Err(ListError::InvalidCursor);
+        }
+
+        let key = key_value(options.sort, &cursor.key)?;
+        let after = match options.order {
+            SortOrder::Asc => Condition::any()
+                .add(Expr::expr(sort.clone()).gt(key.clone()))
+                .add(
+                    Condition::all()
+                        .add(Expr::expr(sort.clone()).eq(key))
+                        .add(users::Column::Id.gt(cursor.id)),
+                ),
+            SortOrder::Desc => Condition::any()
+                .add(Expr::expr(sort.clone()).lt(key.clone()))
+                .add(
+                    Condition::all()
+                        .add(Expr::expr(sort.clone()).eq(key))
+                        .add(users::Column::Id.lt(cursor.id)),
+                ),
+        };
+
+        select = select.filter(after);
+    }
+
+    select = select
+        .order_by(sort, order.clone())
+        .order_by(users::Column::Id, order);
+
+    // Grab one extra member to know whether there's another page
+    let mut users = select.limit(limit + 1).all(&state.db).await?;
+
+    let next_cursor = if users.len() as u64 > limit {
+        users.truncate(limit as usize);
+        let last = users.last().expect("Page can't be empty");
+        Some(encode_cursor(&Cursor {
+            sort: options.sort,
+            key: key_of(state, scope, options.sort, last).await?,
+            id: last.id,
+        }))
+    } else {
+        None
+    };
+
+    Ok(Page { users, next_cursor })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::petring::database::entities::UserModel;
+    use sea_orm::{ActiveModelTrait, Set};
+
+    // id, position, joined, name. Every key has ties except the name, and
+    // none of them follow the id.
+    const MEMBERS: [(i32, i32, &str, &str); 7] = [
+        (1, 2, "2026-10-02T00:00:00+00:00", "fern"),
+        (2, 0, "2026-10-01T00:00:00+00:00", "moss"),
+        (3, 2, "2026-10-02T00:00:00+00:00", "ash"),
+        (4, 1, "2026-10-01T00:00:00+00:00", "reed"),
+        (5, 0, "2026-10-03T00:00:00+00:00", "birch"),
+        (6, 2, "2026-10-01T00:00:00+00:00", "oak"),
+        (7, 1, "2026-10-03T00:00:00+00:00", "elm"),
+    ];
+
+    async fn seeded() -> AppState {
+        let state = AppState::for_tests().await;
+        for (id, position, joined, name) in MEMBERS {
+            UserModel {
+                id: Set(id),
+                username: Set(name.to_string()),
+                username_key: Set(name.to_string()),
+                discord_id: Set(id as i64),
+                url: Set(format!("https://{name}.test/")),
+                url_canonical: Set(format!("https://{name}.test")),
+                status: Set(Status::Approved),
+                created_at: Set(joined.to_string()),
+                position: Set(position),
+                ..Default::default()
+            }
+            .insert(&state.db)
+            .await
+            .unwrap();
+        }
+        state
+    }
+
+    fn options(sort: SortBy, order: SortOrder, cursor: Option<String>) -> ListOptions {
+        ListOptions {
+            cursor,
+            limit: Some(2),
+            sort,
+            order,
+            tag: None,
+            health: None,
+            status: None,
+        }
+    }
+
+    fn expected(sort: SortBy, order: SortOrder) -> Vec<i32> {
+        let mut members = MEMBERS.to_vec();
+        members.sort_by(|a, b| {
+            let keys = match sort {
+                SortBy::Position => a.1.cmp(&b.1),
+                SortBy::Joined => a.2.cmp(b.2),
+                SortBy::Name => a.3.cmp(b.3),
+            };
+            keys.then(a.0.cmp(&b.0))
+        });
+        if order == SortOrder::Desc {
+            members.reverse();
+        }
+        members.into_iter().map(|member| member.0).collect()
+    }
+
+    #[tokio::test]
+    async fn cursors_walk_every_member_once_in_every_sort() {
+        let state = seeded().await;
+
+        for sort in [SortBy::Position, SortBy::Joined, SortBy::Name] {
+            for order in [SortOrder::Asc, SortOrder::Desc] {
+                let mut ids = Vec::new();
+                let mut cursor = None;
+                loop {
+                    let page = list(
+                        &state,
+                        Scope::Default,
+                        Users::find(),
+                        &options(sort, order, cursor),
+                    )
+                    .await
+                    .unwrap_or_else(|_| panic!("Failed to list {sort:?} {order:?}"));
+
+                    assert!(page.users.len() <= 2);
+                    ids.extend(page.users.iter().map(|user| user.id));
+                    cursor = page.next_cursor;
+                    if cursor.is_none() {
+                        break;
+                    }
+                }
+
+                assert_eq!(ids, expected(sort, order), "{sort:?} {order:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn cursors_only_work_with_their_own_sort() {
+        let state = seeded().await;
+        let page = list(
+            &state,
+            Scope::Default,
+            Users::find(),
+            &options(SortBy::Position, SortOrder::Asc, None),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("Failed to list"));
+
+        let result = list(
+            &state,
+            Scope::Default,
+            Users::find(),
+            &options(SortBy::Name, SortOrder::Asc, page.next_cursor),
+        )
+        .await;
+        assert!(matches!(result, Err(ListError::InvalidCursor)));
+    }
+
+    #[tokio::test]
+    async fn broken_cursors_are_rejected() {
+        let state = seeded().await;
+
+        let not_base64 = Some("not a cursor!".to_string());
+        let not_a_number = Some(encode_cursor(&Cursor {
+            sort: SortBy::Position,
+            key: "first".to_string(),
+            id: 1,
+        }));
+
+        for cursor in [not_base64, not_a_number] {
+            let result = list(
+                &state,
+                Scope::Default,
+                Users::find(),
+                &options(SortBy::Position, SortOrder::Asc, cursor),
+            )
+            .await;
+            assert!(matches!(result, Err(ListError::InvalidCursor)));
+        }
+    }
+}
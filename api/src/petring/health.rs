@@ -0,0 +1,162 @@
+use super::{
+    database::{
+        Status,
+        entities::{LinkHealth, LinkHealthModel, Users},
+        link_health, users,
+    },
+    state::AppState,
+};
+use chrono::{Duration as ChronoDuration, Utc};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter, Set};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{sync::Semaphore, task::JoinSet};
+#[allow(unused_imports)]
+use tracing::{debug, error, info, warn};
+
+/* Link health checker
+ *
+ * Periodically probes every approved member's url and keeps one row per
+ * member in `link_health`, ring navigation uses `down_since` to skip sites
+ * that have been broken for a while.
+ */
+
+// Probes in flight at once, so a big ring doesn't open hundreds of sockets
+const MAX_CONCURRENT_PROBES: usize = 16;
+
+struct Probe {
+    user_id: i32,
+    status_code: Option<u16>,
+    latency_ms: i64,
+    error: String,
+}
+
+impl Probe {
+    fn is_up(&self) -> bool {
+        self.status_code
+            .is_some_and(|status| (200..400).contains(&status))
+    }
+}
+
+pub fn spawn_health_checker(state: AppState) {
+    if !state.health.enable {
+        info!("Link health checker is disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(state.health.interval_secs));
+
+        loop {
+            interval.tick().await;
+
+            match check_all(&state).await {
+                Ok(checked) => debug!("Checked link health for {checked} members"),
+                Err(e) => error!("Failed to check link health: {e}"),
+            }
+        }
+    });
+}
+
+// The cutoff before which a member that is still down counts as offline.
+pub fn offline_cutoff(state: &AppState) -> String {
+    (Utc::now() - ChronoDuration::seconds(state.health.offline_after_secs as i64)).to_rfc3339()
+}
+
+pub fn is_offline(state: &AppState, health: &link_health::Model) -> bool {
+    !health.down_since.is_empty() && health.down_since < offline_cutoff(state)
+}
+
+async fn probe(client: reqwest::Client, user: users::Model, timeout: Duration) -> Probe {
+    let start = Instant::now();
+    let response = client.get(&user.url).timeout(timeout).send().await;
+    let latency_ms = start.elapsed().as_millis() as i64;
+
+    match response {
+        Ok(response) => Probe {
+            user_id: user.id,
+            status_code: Some(response.status().as_u16()),
+            latency_ms,
+            error: "".to_string(),
+        },
+        Err(e) => Probe {
+            user_id: user.id,
+            status_code: e.status().map(|status| status.as_u16()),
+            latency_ms,
+            error: e.to_string(),
+        },
+    }
+}
+
+async fn check_all(state: &AppState) -> Result<usize, DbErr> {
+    let users = Users::find()
+        .filter(users::Column::Status.eq(Status::Approved))
+        .all(&state.db)
+        .await?;
+
+    let timeout = Duration::from_secs(state.health.timeout_secs);
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_PROBES));
+    let mut probes = JoinSet::new();
+    for user in users {
+        let client = state.http.clone();
+        let permits = permits.clone();
+        probes.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            probe(client, user, timeout).await
+        });
+    }
+
+    let mut checked = 0;
+    while let Some(probe) = probes.join_next().await {
+        match probe {
+            // One member failing to save shouldn't cost everyone else theirs
+            Ok(probe) => {
+                let user_id = probe.user_id;
+                match record(state, probe).await {
+                    Ok(()) => checked += 1,
+                    Err(e) => warn!("Failed to record link health for user {user_id}: {e}"),
+                }
+            }
+            Err(e) => warn!("Link probe panicked: {e}"),
+        }
+    }
+
+    Ok(checked)
+}
+
+async fn record(state: &AppState, probe: Probe) -> Result<(), DbErr> {
+    let now = Utc::now().to_rfc3339();
+    let is_up = probe.is_up();
+
+    let existing = LinkHealth::find()
+        .filter(link_health::Column::UserId.eq(probe.user_id))
+        .one(&state.db)
+        .await?;
+
+    let mut active: LinkHealthModel = match existing.clone() {
+        Some(existing) => existing.into(),
+        None => LinkHealthModel {
+            user_id: Set(probe.user_id),
+            last_up_at: Set("".to_string()),
+            down_since: Set("".to_string()),
+            ..Default::default()
+        },
+    };
+
+    active.status_code = Set(probe.status_code.map(i32::from));
+    active.latency_ms = Set(Some(probe.latency_ms));
+    active.error = Set(probe.error);
+    active.last_checked_at = Set(now.clone());
+
+    if is_up {
+        active.last_up_at = Set(now);
+        active.down_since = Set("".to_string());
+    } else if existing.is_none_or(|existing| existing.down_since.is_empty()) {
+        active.down_since = Set(now);
+    }
+
+    active.save(&state.db).await?;
+    Ok(())
+}
@@ -95,6 +95,15 @@ impl TokenSecrets {
         }
     }
 
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Self {
+            access_secret: "access".to_string(),
+            refresh_secret: "refresh".to_string(),
+            blacklist: TokenBlacklist::new(),
+        }
+    }
+
     pub fn access_secret(&self) -> &str {
         &self.access_secret
     }
@@ -0,0 +1,395 @@
+use super::{
+    database::{
+        Status,
+        entities::{LinkHealth, RingMembers, Rings, UsernameAliases, Users},
+        link_health, ring_members, rings, username_aliases, users,
+    },
+    health,
+    state::AppState,
+    username,
+};
+use rand::{SeedableRng, rngs::StdRng, seq::IndexedRandom};
+use sea_orm::{
+    ColumnTrait, Condition, DbErr, EntityTrait, JoinType, Order, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait, RelationTrait, Select,
+    sea_query::{Expr, SimpleExpr},
+};
+
+/* Ring navigation
+ *
+ * Every public endpoint resolves members through here, so there's exactly
+ * one idea of who is "in" the ring at any given moment.
+ */
+
+// Which ring we're navigating, the default ring is every member in
+// `users.position` order, sub-rings use their own ordering in `ring_members`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    Default,
+    Ring(i32),
+}
+
+// A member visitors can be sent to: approved, not hidden and not offline.
+pub fn eligible(state: &AppState) -> Condition {
+    let condition = Condition::all()
+        .add(users::Column::Status.eq(Status::Approved))
+        .add(users::Column::Hidden.eq(false));
+
+    if !state.health.enable {
+        return condition;
+    }
+
+    let offline = LinkHealth::find()
+        .select_only()
+        .column(link_health::Column::UserId)
+        .filter(link_health::Column::DownSince.ne(""))
+        .filter(link_health::Column::DownSince.lt(health::offline_cutoff(state)))
+        .into_query();
+
+    condition.add(users::Column::Id.not_in_subquery(offline))
+}
+
+pub async fn find_ring(state: &AppState, slug: &str) -> Result<Option<rings::Model>, DbErr> {
+    Rings::find()
+        .filter(rings::Column::Slug.eq(slug))
+        .one(&state.db)
+        .await
+}
+
+pub fn position(scope: Scope) -> SimpleExpr {
+    match scope {
+        Scope::Default => Expr::col((Users, users::Column::Position)).into(),
+        Scope::Ring(_) => Expr::col((RingMembers, ring_members::Column::Position)).into(),
+    }
+}
+
+pub fn members(scope: Scope) -> Select<Users> {
+    match scope {
+        Scope::Default => Users::find(),
+        Scope::Ring(ring_id) => Users::find()
+            .join(
+                JoinType::InnerJoin,
+                ring_members::Relation::Users.def().rev(),
+            )
+            .filter(ring_members::Column::RingId.eq(ring_id)),
+    }
+}
+
+// All eligible members, in ring order.
+pub fn eligible_members(state: &AppState, scope: Scope) -> Select<Users> {
+    members(scope)
+        .filter(eligible(state))
+        .order_by(position(scope), Order::Asc)
+        .order_by_asc(users::Column::Id)
+}
+
+fn eligible_members_desc(state: &AppState, scope: Scope) -> Select<Users> {
+    members(scope)
+        .filter(eligible(state))
+        .order_by(position(scope), Order::Desc)
+        .order_by_desc(users::Column::Id)
+}
+
+// Looks up the member navigation starts from, this only requires them to be
+// approved so hidden members can still send visitors around the ring. Names
+// a member had before a rename lead to them as well.
+pub async fn find_member(
+    state: &AppState,
+    scope: Scope,
+    username: &str,
+) -> Result<Option<users::Model>, DbErr> {
+    let key = username::key(username);
+
+    let current = members(scope)
+        .filter(users::Column::UsernameKey.eq(key.clone()))
+        .filter(users::Column::Status.eq(Status::Approved))
+        .one(&state.db)
+        .await?;
+
+    if current.is_some() {
+        return Ok(current);
+    }
+
+    let alias = match UsernameAliases::find()
+        .filter(username_aliases::Column::UsernameKey.eq(key))
+        .one(&state.db)
+        .await?
+    {
+        Some(alias) => alias,
+        None => return Ok(None),
+    };
+
+    members(scope)
+        .filter(users::Column::Id.eq(alias.user_id))
+        .filter(users::Column::Status.eq(Status::Approved))
+        .one(&state.db)
+        .await
+}
+
+pub async fn position_of(
+    state: &AppState,
+    scope: Scope,
+    member: &users::Model,
+) -> Result<i32, DbErr> {
+    match scope {
+        Scope::Default => Ok(member.position),
+        Scope::Ring(ring_id) => {
+            let ring_member = RingMembers::find()
+                .filter(ring_members::Column::RingId.eq(ring_id))
+                .filter(ring_members::Column::UserId.eq(member.id))
+                .one(&state.db)
+                .await?;
+
+            ring_member
+                .map(|ring_member| ring_member.position)
+                .ok_or_else(|| DbErr::RecordNotFound("Ring member not found".to_string()))
+        }
+    }
+}
+
+// Two members can end up with the same position, the id breaks the tie so
+// neither of them gets skipped
+fn after(scope: Scope, position_key: i32, id: i32) -> Condition {
+    Condition::any()
+        .add(Expr::expr(position(scope)).gt(position_key))
+        .add(
+            Condition::all()
+                .add(Expr::expr(position(scope)).eq(position_key))
+                .add(users::Column::Id.gt(id)),
+        )
+}
+
+fn before(scope: Scope, position_key: i32, id: i32) -> Condition {
+    Condition::any()
+        .add(Expr::expr(position(scope)).lt(position_key))
+        .add(
+            Condition::all()
+                .add(Expr::expr(position(scope)).eq(position_key))
+                .add(users::Column::Id.lt(id)),
+        )
+}
+
+pub async fn next_member(
+    state: &AppState,
+    scope: Scope,
+    member: &users::Model,
+) -> Result<Option<users::Model>, DbErr> {
+    let current = position_of(state, scope, member).await?;
+    let next = eligible_members(state, scope)
+        .filter(after(scope, current, member.id))
+        .one(&state.db)
+        .await?;
+
+    match next {
+        Some(next) => Ok(Some(next)),
+        None => eligible_members(state, scope).one(&state.db).await,
+    }
+}
+
+pub async fn prev_member(
+    state: &AppState,
+    scope: Scope,
+    member: &users::Model,
+) -> Result<Option<users::Model>, DbErr> {
+    let current = position_of(state, scope, member).await?;
+    let prev = eligible_members_desc(state, scope)
+        .filter(before(scope, current, member.id))
+        .one(&state.db)
+        .await?;
+
+    match prev {
+        Some(prev) => Ok(Some(prev)),
+        None => eligible_members_desc(state, scope).one(&state.db).await,
+    }
+}
+
+// Picks a random member, seeded by `member` so each site gets a stable pick
+// for as long as the ring doesn't change.
+pub async fn random_member(
+    state: &AppState,
+    scope: Scope,
+    member: &users::Model,
+) -> Result<Option<users::Model>, DbErr> {
+    let user_seed = member.id << 16 | member.id;
+    let mut rng = StdRng::seed_from_u64(user_seed as u64);
+
+    let members = eligible_members(state, scope).all(&state.db).await?;
+    Ok(members.choose(&mut rng).cloned())
+}
+
+pub async fn any_random_member(
+    state: &AppState,
+    scope: Scope,
+) -> Result<Option<users::Model>, DbErr> {
+    let members = eligible_members(state, scope).all(&state.db).await?;
+    Ok(members.choose(&mut rand::rng()).cloned())
+}
+
+pub struct Neighbors {
+    pub prev: Option<users::Model>,
+    pub next: Option<users::Model>,
+    pub random: Option<users::Model>,
+}
+
+pub async fn neighbors(
+    state: &AppState,
+    scope: Scope,
+    member: &users::Model,
+) -> Result<Neighbors, DbErr> {
+    Ok(Neighbors {
+        prev: prev_member(state, scope, member).await?,
+        next: next_member(state, scope, member).await?,
+        random: random_member(state, scope, member).await?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::petring::database::entities::{RingMemberModel, RingModel, UserModel};
+    use sea_orm::{ActiveModelTrait, Set};
+
+    const CREATED_AT: &str = "2026-10-17T00:00:00+00:00";
+
+    async fn join(state: &AppState, id: i32, position: i32) -> users::Model {
+        let name = format!("member{id}");
+        UserModel {
+            id: Set(id),
+            username: Set(name.clone()),
+            username_key: Set(name.clone()),
+            discord_id: Set(id as i64),
+            url: Set(format!("https://{name}.test/")),
+            url_canonical: Set(format!("https://{name}.test")),
+            status: Set(Status::Approved),
+            created_at: Set(CREATED_AT.to_string()),
+            position: Set(position),
+            ..Default::default()
+        }
+        .insert(&state.db)
+        .await
+        .unwrap()
+    }
+
+    // Ids of the members `steps` hops away from `from`, one at a time
+    async fn walk(
+        state: &AppState,
+        scope: Scope,
+        from: &users::Model,
+        steps: usize,
+        forward: bool,
+    ) -> Vec<i32> {
+        let mut at = from.clone();
+        let mut ids = Vec::new();
+        for _ in 0..steps {
+            at = if forward {
+                next_member(state, scope, &at).await.unwrap().unwrap()
+            } else {
+                prev_member(state, scope, &at).await.unwrap().unwrap()
+            };
+            ids.push(at.id);
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn wraps_around_both_ends() {
+        let state = AppState::for_tests().await;
+        let first = join(&state, 1, 0).await;
+        join(&state, 2, 1).await;
+        let last = join(&state, 3, 2).await;
+
+        assert_eq!(walk(&state, Scope::Default, &last, 1, true).await, [1]);
+        assert_eq!(walk(&state, Scope::Default, &first, 1, false).await, [3]);
+        assert_eq!(
+            walk(&state, Scope::Default, &first, 4, true).await,
+            [2, 3, 1, 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn equal_positions_go_by_id() {
+        let state = AppState::for_tests().await;
+        join(&state, 1, 1).await;
+        let start = join(&state, 2, 0).await;
+        join(&state, 3, 1).await;
+        join(&state, 4, 0).await;
+
+        // Ring order is 2, 4, 1, 3
+        assert_eq!(
+            walk(&state, Scope::Default, &start, 4, true).await,
+            [4, 1, 3, 2]
+        );
+        assert_eq!(
+            walk(&state, Scope::Default, &start, 4, false).await,
+            [3, 1, 4, 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_lone_member_leads_to_themselves() {
+        let state = AppState::for_tests().await;
+        let only = join(&state, 1, 0).await;
+
+        assert_eq!(walk(&state, Scope::Default, &only, 2, true).await, [1, 1]);
+        assert_eq!(walk(&state, Scope::Default, &only, 2, false).await, [1, 1]);
+    }
+
+    #[tokio::test]
+    async fn hidden_members_are_skipped_but_can_still_leave() {
+        let state = AppState::for_tests().await;
+        let first = join(&state, 1, 0).await;
+        let hidden = join(&state, 2, 1).await;
+        join(&state, 3, 2).await;
+
+        let mut active: UserModel = hidden.clone().into();
+        active.hidden = Set(true);
+        let hidden = active.update(&state.db).await.unwrap();
+
+        assert_eq!(walk(&state, Scope::Default, &first, 2, true).await, [3, 1]);
+        assert_eq!(walk(&state, Scope::Default, &hidden, 1, true).await, [3]);
+        assert_eq!(walk(&state, Scope::Default, &hidden, 1, false).await, [1]);
+    }
+
+    #[tokio::test]
+    async fn sub_rings_use_their_own_positions() {
+        let state = AppState::for_tests().await;
+        let ring = RingModel {
+            slug: Set("sub".to_string()),
+            name: Set("sub".to_string()),
+            created_at: Set(CREATED_AT.to_string()),
+            ..Default::default()
+        }
+        .insert(&state.db)
+        .await
+        .unwrap();
+
+        // Positions in the default ring run the other way around
+        let mut members = Vec::new();
+        for (id, position) in [(1, 0), (2, 1), (3, 1), (4, 2)] {
+            let member = join(&state, id, 10 - id).await;
+            RingMemberModel {
+                ring_id: Set(ring.id),
+                user_id: Set(member.id),
+                position: Set(position),
+                created_at: Set(CREATED_AT.to_string()),
+                ..Default::default()
+            }
+            .insert(&state.db)
+            .await
+            .unwrap();
+            members.push(member);
+        }
+        // Not in the sub-ring at all
+        join(&state, 5, 0).await;
+
+        let scope = Scope::Ring(ring.id);
+        assert_eq!(
+            walk(&state, scope, &members[0], 4, true).await,
+            [2, 3, 4, 1]
+        );
+        assert_eq!(
+            walk(&state, scope, &members[0], 4, false).await,
+            [4, 3, 2, 1]
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use super::{
+    api::AdResponse,
+    audit::{self, Audit},
+    database::{
+        Status, ads,
+        entities::{AdModel, Ads, Users},
+        users,
+    },
+    state::AppState,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use rand::seq::IndexedRandom;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+    QuerySelect, QueryTrait, Select, Set, sea_query::Expr,
+};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+#[allow(unused_imports)]
+use tracing::{debug, error, info, warn};
+
+/* Ad rotation
+ *
+ * Serves whichever eligible ad has been shown the least for its weight, so
+ * every member gets their share even over a few minutes. An ad with weight
+ * 2 is shown twice as often as one with weight 1.
+ *
+ * The ad iframe polls a lot, so impressions are counted in memory and added
+ * to `ads.impressions` every `flush_interval_secs`. Whatever hasn't been
+ * flushed when the process stops is lost, which only costs a bit of
+ * fairness. Daily stats for the ad owners are flushed alongside, see
+ * `ad_stats`.
+ *
+ * Ads only run between their `starts_at` and `ends_at`, and approvals run
+ * out after `ad_approval.lifetime_days`. Expired ones are put back in the
+ * queue on the same interval.
+ */
+
+pub const MAX_WEIGHT: i32 = 10;
+
+// How long an approval lasts, `None` when it doesn't run out
+fn approval_lifetime(state: &AppState) -> Option<ChronoDuration> {
+    match state.ad_approval.lifetime_days {
+        0 => None,
+        days => Some(ChronoDuration::days(days as i64)),
+    }
+}
+
+// Ads approved before this need to be approved again
+fn approval_cutoff(state: &AppState) -> Option<String> {
+    approval_lifetime(state).map(|lifetime| (Utc::now() - lifetime).to_rfc3339())
+}
+
+// When the approval of an approved ad runs out, `None` if it doesn't
+pub fn approval_expiry(state: &AppState, ad: &ads::Model) -> Option<DateTime<Utc>> {
+    let lifetime = approval_lifetime(state)?;
+    let verified_at = DateTime::parse_from_rfc3339(&ad.verified_at).ok()?;
+
+    Some(verified_at.with_timezone(&Utc) + lifetime)
+}
+
+// Approved ads of approved members that are inside their campaign and whose
+// approval hasn't run out yet. Suspending a member takes their ad down with
+// them.
+pub fn eligible(state: &AppState) -> Select<Ads> {
+    let approved_users = Users::find()
+        .select_only()
+        .column(users::Column::DiscordId)
+        .filter(users::Column::Status.eq(Status::Approved))
+        .into_query();
+
+    let now = Utc::now().to_rfc3339();
+
+    let mut select = Ads::find()
+        .filter(ads::Column::Status.eq(Status::Approved))
+        .filter(ads::Column::DiscordId.in_subquery(approved_users))
+        .filter(
+            Condition::any()
+                .add(ads::Column::StartsAt.eq(""))
+                .add(ads::Column::StartsAt.lte(now.clone())),
+        )
+        .filter(
+            Condition::any()
+                .add(ads::Column::EndsAt.eq(""))
+                .add(ads::Column::EndsAt.gt(now)),
+        );
+
+    if let Some(cutoff) = approval_cutoff(state) {
+        select = select.filter(ads::Column::VerifiedAt.gt(cutoff));
+    }
+
+    select
+}
+
+// Puts ads whose approval ran out back in the queue
+pub async fn expire_approvals(state: &AppState) -> Result<usize, DbErr> {
+    let cutoff = match approval_cutoff(state) {
+        Some(cutoff) => cutoff,
+        None => return Ok(0),
+    };
+
+    let expired = Ads::find()
+        .filter(ads::Column::Status.eq(Status::Approved))
+        .filter(ads::Column::VerifiedAt.lte(cutoff))
+        .all(&state.db)
+        .await?;
+
+    let audit = Audit::system("ad approval expiry");
+    let now = Utc::now().to_rfc3339();
+
+    for ad in &expired {
+        let before = audit::snapshot(&AdResponse::from(ad));
+        let mut active_ad: AdModel = ad.clone().into();
+        active_ad.status = Set(Status::Pending);
+        active_ad.status_reason = Set("Approval expired".to_string());
+        active_ad.moderated_by = Set(None);
+        active_ad.moderated_at = Set(now.clone());
+
+        let updated = active_ad.update(&state.db).await?;
+        audit
+            .record(
+                state,
+                [ad.discord_id as u64],
+                before,
+                audit::snapshot(&AdResponse::from(&updated)),
+            )
+            .await;
+    }
+
+    Ok(expired.len())
+}
+
+#[derive(Default)]
+pub struct Rotation {
+    // Impressions per ad id since the last flush
+    pending: Mutex<HashMap<i32, i64>>,
+}
+
+fn shown(pending: &HashMap<i32, i64>, ad: &ads::Model) -> i64 {
+    ad.impressions + pending.get(&ad.id).copied().unwrap_or_default()
+}
+
+// Impressions per weight, multiplied out so nothing gets rounded
+fn compare(pending: &HashMap<i32, i64>, a: &ads::Model, b: &ads::Model) -> Ordering {
+    let a_share = shown(pending, a) as i128 * b.weight.max(1) as i128;
+    let b_share = shown(pending, b) as i128 * a.weight.max(1) as i128;
+    a_share.cmp(&b_share)
+}
+
+impl Rotation {
+    fn pending(&self) -> MutexGuard<'_, HashMap<i32, i64>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Picks the next ad to show and counts the impression
+    pub fn pick<'a>(&self, ads: &'a [ads::Model]) -> Option<&'a ads::Model> {
+        let mut pending = self.pending();
+
+        let least = ads.iter().min_by(|a, b| compare(&pending, a, b))?;
+        let tied: Vec<&ads::Model> = ads
+            .iter()
+            .filter(|ad| compare(&pending, ad, least) == Ordering::Equal)
+            .collect();
+
+        let ad = *tied.choose(&mut rand::rng())?;
+        *pending.entry(ad.id).or_default() += 1;
+
+        Some(ad)
+    }
+
+    // Where a newly approved ad starts counting from. Starting at 0 would
+    // give it every impression until it caught up with the others.
+    pub fn baseline(&self, ads: &[ads::Model], weight: i32) -> i64 {
+        let pending = self.pending();
+
+        ads.iter()
+            .map(|ad| shown(&pending, ad) * weight.max(1) as i64 / ad.weight.max(1) as i64)
+            .min()
+            .unwrap_or_default()
+    }
+
+    pub async fn flush(&self, db: &DatabaseConnection) -> Result<usize, DbErr> {
+        let pending = std::mem::take(&mut *self.pending());
+        let mut remaining = pending.clone();
+
+        for (id, count) in &pending {
+            let result = Ads::update_many()
+                .col_expr(
+                    ads::Column::Impressions,
+                    Expr::col(ads::Column::Impressions).add(*count),
+                )
+                .filter(ads::Column::Id.eq(*id))
+                .exec(db)
+                .await;
+
+            if let Err(e) = result {
+                // Keep what wasn't written for the next flush
+                let mut pending = self.pending();
+                for (id, count) in remaining {
+                    *pending.entry(id).or_default() += count;
+                }
+                return Err(e);
+            }
+
+            remaining.remove(id);
+        }
+
+        Ok(pending.len())
+    }
+}
+
+pub fn spawn_flusher(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            state.ad_rotation.flush_interval_secs.max(1),
+        ));
+
+        loop {
+            interval.tick().await;
+
+            match state.rotation.flush(&state.db).await {
+                Ok(0) => {}
+                Ok(flushed) => debug!("Flushed impressions for {flushed} ads"),
+                Err(e) => error!("Failed to flush ad impressions: {e}"),
+            }
+
+            match state.ad_stats.flush(&state.db).await {
+                Ok(0) => {}
+                Ok(flushed) => debug!("Flushed daily stats for {flushed} ads"),
+                Err(e) => error!("Failed to flush ad stats: {e}"),
+            }
+
+            match expire_approvals(&state).await {
+                Ok(0) => {}
+                Ok(expired) => info!("Approval ran out for {expired} ads"),
+                Err(e) => error!("Failed to expire ad approvals: {e}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::petring::database::AdFormat;
+
+    fn ad(id: i32, weight: i32, impressions: i64) -> ads::Model {
+        ads::Model {
+            id,
+            username: format!("member{id}"),
+            discord_id: id as i64,
+            format: AdFormat::Button,
+            image_url: format!("https://member{id}.test/ad.png"),
+            image_url_canonical: format!("https://member{id}.test/ad.png"),
+            image_hash: String::new(),
+            image_width: 88,
+            image_height: 31,
+            weight,
+            impressions,
+            starts_at: String::new(),
+            ends_at: String::new(),
+            ad_url: format!("https://member{id}.test/"),
+            status: Status::Approved,
+            status_reason: String::new(),
+            moderated_by: None,
+            moderated_at: String::new(),
+            created_at: String::new(),
+            edited_at: String::new(),
+            verified_at: String::new(),
+        }
+    }
+
+    fn picks(rotation: &Rotation, ads: &[ads::Model], times: usize) -> HashMap<i32, usize> {
+        let mut picked = HashMap::new();
+        for _ in 0..times {
+            let ad = rotation.pick(ads).unwrap();
+            *picked.entry(ad.id).or_default() += 1;
+        }
+        picked
+    }
+
+    #[test]
+    fn equal_ads_take_turns() {
+        let rotation = Rotation::default();
+        let ads = [ad(1, 1, 0), ad(2, 1, 0), ad(3, 1, 0)];
+
+        // Whatever the ties land on, nobody gets a second turn before the
+        // others had their first
+        for _ in 0..50 {
+            let round = picks(&rotation, &ads, 3);
+            assert_eq!(round.len(), 3);
+        }
+    }
+
+    #[test]
+    fn weights_set_the_share() {
+        let rotation = Rotation::default();
+        let ads = [ad(1, 1, 0), ad(2, 3, 0)];
+
+        let picked = picks(&rotation, &ads, 400);
+        assert_eq!(picked[&1], 100);
+        assert_eq!(picked[&2], 300);
+    }
+
+    #[test]
+    fn flushed_and_pending_impressions_both_count() {
+        let rotation = Rotation::default();
+        let ads = [ad(1, 1, 10), ad(2, 1, 0)];
+
+        assert_eq!(picks(&rotation, &ads, 10)[&2], 10);
+        // Level now, back to taking turns
+        let picked = picks(&rotation, &ads, 10);
+        assert_eq!((picked[&1], picked[&2]), (5, 5));
+    }
+
+    #[test]
+    fn newcomers_start_level_instead_of_catching_up() {
+        let rotation = Rotation::default();
+        let running = [ad(1, 1, 40), ad(2, 2, 100)];
+        picks(&rotation, &running, 2);
+
+        // Both picks go to ad 1, it's still the least shown for its weight
+        assert_eq!(rotation.baseline(&running, 1), 42);
+        assert_eq!(rotation.baseline(&running, 2), 84);
+
+        let joined = ad(3, 1, rotation.baseline(&running, 1));
+        let ads = [running[0].clone(), running[1].clone(), joined];
+        let picked = picks(&rotation, &ads, 40);
+        assert_eq!((picked[&1], picked[&2], picked[&3]), (14, 12, 14));
+    }
+
+    #[test]
+    fn nothing_to_pick_from() {
+        let rotation = Rotation::default();
+        assert!(rotation.pick(&[]).is_none());
+        assert_eq!(rotation.baseline(&[], 1), 0);
+    }
+}
@@ -0,0 +1,186 @@
+use super::{
+    database::{entities::Users, users},
+    ring::{self, Scope},
+    state::AppState,
+};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbErr, EntityTrait, FromQueryResult, QueryFilter, QuerySelect,
+    QueryTrait, Statement,
+};
+
+/* Member search
+ *
+ * Backed by the `users_fts` FTS5 table, matching happens there, narrowed
+ * down to members the ring would send people to.
+ */
+
+pub const MAX_RESULTS: u64 = 100;
+
+// Wrapped around matches in snippets and swapped for `<mark>` after escaping,
+// descriptions can't contain control characters so these are unambiguous.
+const MATCH_START: &str = "\u{2}";
+const MATCH_END: &str = "\u{3}";
+
+#[derive(FromQueryResult)]
+struct Match {
+    id: i32,
+    snippet: String,
+    rank: f64,
+}
+
+pub struct SearchResult {
+    pub user: users::Model,
+    pub snippet: String,
+    pub rank: f64,
+}
+
+// Turns free-form input into an FTS5 query: every word has to match, as a
+// prefix, and quoting keeps FTS5 operators in the input from doing anything.
+fn fts_query(q: &str) -> Option<String> {
+    let terms: Vec<String> = q
+        .split_whitespace()
+        .map(|term| {
+            term.chars()
+                .filter(|c| *c != '"' && !c.is_control())
+                .collect::<String>()
+        })
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{term}\"*"))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn highlight(snippet: &str) -> String {
+    escape_html(snippet)
+        .replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
+}
+
+pub async fn search(
+    state: &AppState,
+    scope: Scope,
+    q: &str,
+    limit: u64,
+) -> Result<Vec<SearchResult>, DbErr> {
+    let query = match fts_query(q) {
+        Some(query) => query,
+        None => return Ok(Vec::new()),
+    };
+
+    let backend = state.db.get_database_backend();
+
+    // Narrowed down inside the FTS query so the limit only counts members the
+    // ring would send people to
+    let eligible = ring::members(scope)
+        .filter(ring::eligible(state))
+        .select_only()
+        .column(users::Column::Id)
+        .build(backend);
+
+    let mut values = vec![MATCH_START.into(), MATCH_END.into(), query.into()];
+    values.extend(eligible.values.map(|values| values.0).unwrap_or_default());
+    values.push((limit as i64).into());
+
+    // Usernames weigh the most, then tags, then descriptions
+    let statement = Statement::from_sql_and_values(
+        backend,
+        format!(
+            r#"SELECT "rowid" AS "id",
+                snippet("users_fts", -1, ?, ?, '…', 12) AS "snippet",
+                bm25("users_fts", 10.0, 1.0, 5.0) AS "rank"
+            FROM "users_fts"
+            WHERE "users_fts" MATCH ? AND "rowid" IN ({})
+            ORDER BY "rank"
+            LIMIT ?"#,
+            eligible.sql
+        ),
+        values,
+    );
+
+    let matches = Match::find_by_statement(statement).all(&state.db).await?;
+
+    let ids: Vec<i32> = matches.iter().map(|found| found.id).collect();
+    let users = Users::find()
+        .filter(users::Column::Id.is_in(ids))
+        .all(&state.db)
+        .await?;
+
+    // Keep the FTS ranking, lower bm25 is better
+    Ok(matches
+        .into_iter()
+        .filter_map(|found| {
+            let user = users.iter().find(|user| user.id == found.id)?;
+            Some(SearchResult {
+                user: user.clone(),
+                snippet: highlight(&found.snippet),
+                rank: found.rank,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_word_is_a_quoted_prefix() {
+        assert_eq!(
+            fts_query("  cat   dog\tbird ").as_deref(),
+            Some(r#""cat"* "dog"* "bird"*"#)
+        );
+    }
+
+    #[test]
+    fn operators_are_plain_words() {
+        assert_eq!(
+            fts_query("cat OR dog NOT bird").as_deref(),
+            Some(r#""cat"* "OR"* "dog"* "NOT"* "bird"*"#)
+        );
+        assert_eq!(
+            fts_query("username:bob -tags ^start NEAR(a b) *").as_deref(),
+            Some(r#""username:bob"* "-tags"* "^start"* "NEAR(a"* "b)"* "*"*"#)
+        );
+    }
+
+    #[test]
+    fn quotes_and_control_characters_are_dropped() {
+        assert_eq!(
+            fts_query(r#"say "hi" it"s"#).as_deref(),
+            Some(r#""say"* "hi"* "its"*"#)
+        );
+        assert_eq!(fts_query("a\u{2}b\u{3}").as_deref(), Some(r#""ab"*"#));
+    }
+
+    #[test]
+    fn nothing_to_search_for() {
+        assert_eq!(fts_query(""), None);
+        assert_eq!(fts_query("   "), None);
+        assert_eq!(fts_query(r#"" "" ""#), None);
+    }
+
+    #[test]
+    fn snippets_are_escaped_before_marking() {
+        assert_eq!(
+            highlight("<b>\u{2}cat\u{3}</b> & 'dog' \"bird\""),
+            "&lt;b&gt;<mark>cat</mark>&lt;/b&gt; &amp; &#39;dog&#39; &quot;bird&quot;"
+        );
+        assert_eq!(
+            highlight("…\u{2}a\u{3} and \u{2}b\u{3}…"),
+            "…<mark>a</mark> and <mark>b</mark>…"
+        );
+    }
+}
@@ -3,7 +3,16 @@ use sea_orm::{ConnectOptions, DatabaseConnection};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::petring::jwt::TokenSecrets;
+use crate::petring::{
+    ad_stats::AdStats,
+    config::{
+        AdApprovalConfig, AdImagesConfig, AdRotationConfig, CommunityConfig, Config, HealthConfig,
+        UrlPolicyConfig,
+    },
+    jwt::TokenSecrets,
+    rotation::Rotation,
+    url_policy,
+};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -11,18 +20,30 @@ pub struct AppState {
     pub has_generated_jwt: Arc<Mutex<bool>>,
     pub bot_token: String,
     pub token_secrets: Arc<Mutex<TokenSecrets>>,
+    pub http: reqwest::Client,
+    pub health: HealthConfig,
+    pub url_policy: Arc<UrlPolicyConfig>,
+    pub ad_images: Arc<AdImagesConfig>,
+    pub ad_rotation: AdRotationConfig,
+    pub ad_approval: AdApprovalConfig,
+    pub rotation: Arc<Rotation>,
+    pub ad_stats: Arc<AdStats>,
+    pub community: Arc<CommunityConfig>,
 }
 
 /* State for the admin endpoints */
 
 impl AppState {
-    pub async fn new() -> Self {
+    pub async fn new(config: &Config, community: &CommunityConfig) -> Self {
         dotenv().ok();
 
-        let bot_token = std::env::var("BOT_TOKEN").expect("BOT_TOKEN must be set");
+        let bot_token = std::env::var(&community.bot_token_env)
+            .unwrap_or_else(|_| panic!("{} must be set", community.bot_token_env));
 
-        let mut connection_opts =
-            ConnectOptions::new(std::env::var("DATABASE_URL").expect("DATABASE_URL must be set"));
+        let mut connection_opts = ConnectOptions::new(
+            std::env::var(&community.database_url_env)
+                .unwrap_or_else(|_| panic!("{} must be set", community.database_url_env)),
+        );
 
         connection_opts
             .sqlx_logging(true)
@@ -36,13 +57,63 @@ impl AppState {
             .await
             .expect("Failed to connect to database");
 
-        let token_secrets = TokenSecrets::new();
+        Self::with_db(config, community, db, bot_token, TokenSecrets::new())
+    }
+
+    fn with_db(
+        config: &Config,
+        community: &CommunityConfig,
+        db: DatabaseConnection,
+        bot_token: String,
+        token_secrets: TokenSecrets,
+    ) -> Self {
+        // Only ever used for urls members gave us
+        let http = url_policy::guarded_client(config.url_policy().resolve_hosts);
 
         Self {
             db,
             has_generated_jwt: Arc::new(Mutex::new(false)),
             bot_token,
             token_secrets: Arc::new(Mutex::new(token_secrets)),
+            http,
+            health: config.health().clone(),
+            url_policy: Arc::new(config.url_policy().clone()),
+            ad_images: Arc::new(config.ad_images().clone()),
+            ad_rotation: config.ad_rotation().clone(),
+            ad_approval: config.ad_approval().clone(),
+            rotation: Arc::new(Rotation::default()),
+            ad_stats: Arc::new(AdStats::new(config.network().behind_proxy)),
+            community: Arc::new(community.clone()),
         }
     }
 }
+
+// A fresh in-memory database with every migration applied. It's a single
+// connection, every connection would get a database of its own otherwise.
+#[cfg(test)]
+impl AppState {
+    pub async fn for_tests() -> Self {
+        use migration::{Migrator, MigratorTrait};
+
+        let mut connection_opts = ConnectOptions::new("sqlite::memory:");
+        connection_opts
+            .sqlx_logging(false)
+            .max_connections(1)
+            .min_connections(1);
+
+        let db = sea_orm::Database::connect(connection_opts)
+            .await
+            .expect("Failed to connect to database");
+        Migrator::up(&db, None)
+            .await
+            .expect("Failed to run migrations");
+
+        Self::with_db(
+            &Config::default(),
+            &CommunityConfig::default(),
+            db,
+            "bot".to_string(),
+            TokenSecrets::for_tests(),
+        )
+    }
+}
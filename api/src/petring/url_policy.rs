@@ -0,0 +1,464 @@
+use super::{
+    bans::normalize_host,
+    database::{RuleAction, UrlKind, entities::UrlRules},
+    state::AppState,
+};
+use reqwest::{
+    Client, Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect,
+};
+use sea_orm::{DbErr, EntityTrait};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::net::lookup_host;
+
+/* URL policy
+ *
+ * Decides which urls members can use for their site and for images. The
+ * rules from `petring-api.toml` are fixed, the ones in `url_rules` can be
+ * changed at runtime. Hosts are matched as a whole or as a parent domain,
+ * so `github.com` doesn't catch `githubusercontent.com`.
+ */
+
+// Names that never point anywhere public
+const SPECIAL_HOSTS: [&str; 6] = [
+    "localhost",
+    "local",
+    "internal",
+    "lan",
+    "home.arpa",
+    "onion",
+];
+
+// Redirects a member's url may go through before we give up
+const MAX_REDIRECTS: usize = 5;
+
+// Query params that only say where a visitor came from
+const TRACKING_PARAMS: [&str; 10] = [
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "ref", "ref_src",
+];
+
+fn tracking_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+/* Canonical urls
+ *
+ * Two urls that lead to the same page should only be in the ring once. The
+ * parser already lowercases the host, turns IDNs into punycode and drops
+ * default ports, on top of that the scheme, fragment, trailing slashes and
+ * tracking params go. `HTTPS://Site.dev/?utm_source=x` and `http://site.dev`
+ * both end up as `site.dev`.
+ */
+pub fn canonical(url: &Url) -> String {
+    let mut canonical = url
+        .host_str()
+        .unwrap_or_default()
+        .trim_end_matches('.')
+        .to_string();
+
+    if let Some(port) = url.port() {
+        canonical.push_str(&format!(":{port}"));
+    }
+
+    canonical.push_str(url.path().trim_end_matches('/'));
+
+    // Split by hand so the remaining params keep their encoding
+    let query: Vec<&str> = url
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| {
+            let name = pair.split_once('=').map_or(*pair, |(name, _)| name);
+            !name.is_empty() && !tracking_param(name)
+        })
+        .collect();
+
+    if !query.is_empty() {
+        canonical.push('?');
+        canonical.push_str(&query.join("&"));
+    }
+
+    canonical
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UrlError {
+    Unparseable,
+    Scheme,
+    Credentials,
+    IpLiteral,
+    PrivateHost,
+    Unresolvable,
+    Denied(String),
+}
+
+impl fmt::Display for UrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unparseable => write!(f, "not a valid url"),
+            Self::Scheme => write!(f, "only http and https urls are allowed"),
+            Self::Credentials => write!(f, "urls can't contain a username or password"),
+            Self::IpLiteral => write!(f, "ip addresses aren't allowed, use a hostname"),
+            Self::PrivateHost => write!(f, "host points at a private address"),
+            Self::Unresolvable => write!(f, "host doesn't resolve"),
+            Self::Denied(host) => write!(f, "{host} isn't allowed"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Rule {
+    // None for rules from the config file
+    pub id: Option<i32>,
+    pub kind: UrlKind,
+    pub action: RuleAction,
+    pub host: String,
+}
+
+impl Rule {
+    fn matches(&self, host: &str) -> bool {
+        host == self.host
+            || host
+                .strip_suffix(&self.host)
+                .is_some_and(|rest| rest.ends_with('.'))
+    }
+}
+
+pub struct UrlPolicy {
+    pub resolve_hosts: bool,
+    pub rules: Vec<Rule>,
+}
+
+fn config_rules(kind: UrlKind, action: RuleAction, hosts: &[String]) -> Vec<Rule> {
+    hosts
+        .iter()
+        .map(|host| Rule {
+            id: None,
+            kind,
+            action,
+            host: normalize_host(host),
+        })
+        .collect()
+}
+
+pub async fn load(state: &AppState) -> Result<UrlPolicy, DbErr> {
+    let config = &state.url_policy;
+    let mut rules = Vec::new();
+    rules.extend(config_rules(
+        UrlKind::Site,
+        RuleAction::Allow,
+        &config.allow_sites,
+    ));
+    rules.extend(config_rules(
+        UrlKind::Site,
+        RuleAction::Deny,
+        &config.deny_sites,
+    ));
+    rules.extend(config_rules(
+        UrlKind::Image,
+        RuleAction::Allow,
+        &config.allow_images,
+    ));
+    rules.extend(config_rules(
+        UrlKind::Image,
+        RuleAction::Deny,
+        &config.deny_images,
+    ));
+
+    for rule in UrlRules::find().all(&state.db).await? {
+        rules.push(Rule {
+            id: Some(rule.id),
+            kind: rule.kind,
+            action: rule.action,
+            host: rule.host,
+        });
+    }
+
+    Ok(UrlPolicy {
+        resolve_hosts: config.resolve_hosts,
+        rules,
+    })
+}
+
+fn private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+        // Benchmarking and reserved, 198.18.0.0/15 and 240.0.0.0/4
+        || (a == 198 && (b == 18 || b == 19))
+        || a >= 240
+}
+
+fn private_v6(ip: Ipv6Addr) -> bool {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return private_v4(ip);
+    }
+
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local, fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // Link local, fe80::/10
+        || (first & 0xffc0) == 0xfe80
+        // Documentation, 2001:db8::/32
+        || (first == 0x2001 && ip.segments()[1] == 0xdb8)
+}
+
+pub fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => private_v4(ip),
+        IpAddr::V6(ip) => private_v6(ip),
+    }
+}
+
+// The checks that don't need the rules or dns, also run on every redirect
+fn check_host(parsed: &Url) -> Result<String, UrlError> {
+    if parsed.scheme() != "http" && parsed.scheme() != "https" {
+        return Err(UrlError::Scheme);
+    }
+
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        return Err(UrlError::Credentials);
+    }
+
+    let host = match parsed.host_str() {
+        Some(host) => normalize_host(host),
+        None => return Err(UrlError::Unparseable),
+    };
+
+    // IPv6 literals keep their brackets in `host_str`
+    if host.trim_matches(['[', ']']).parse::<IpAddr>().is_ok() {
+        return Err(UrlError::IpLiteral);
+    }
+
+    if SPECIAL_HOSTS
+        .iter()
+        .any(|special| host == *special || host.ends_with(&format!(".{special}")))
+    {
+        return Err(UrlError::PrivateHost);
+    }
+
+    Ok(host)
+}
+
+impl UrlPolicy {
+    fn allowed(&self, kind: UrlKind, host: &str) -> bool {
+        let matching = || {
+            self.rules
+                .iter()
+                .filter(move |rule| rule.kind == kind && rule.matches(host))
+        };
+
+        // Allow rules win, so a single subdomain can be let through
+        matching().any(|rule| rule.action == RuleAction::Allow)
+            || !matching().any(|rule| rule.action == RuleAction::Deny)
+    }
+
+    pub async fn check(&self, url: &str, kind: UrlKind) -> Result<Url, UrlError> {
+        let parsed = Url::parse(url.trim()).map_err(|_| UrlError::Unparseable)?;
+        let host = check_host(&parsed)?;
+
+        if !self.allowed(kind, &host) {
+            return Err(UrlError::Denied(host));
+        }
+
+        if self.resolve_hosts {
+            let port = parsed.port_or_known_default().unwrap_or(443);
+            let addresses: Vec<IpAddr> = lookup_host((host.as_str(), port))
+                .await
+                .map_err(|_| UrlError::Unresolvable)?
+                .map(|address| address.ip())
+                .collect();
+
+            if addresses.is_empty() {
+                return Err(UrlError::Unresolvable);
+            }
+
+            if addresses.into_iter().any(is_private) {
+                return Err(UrlError::PrivateHost);
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/* Guarded client
+ *
+ * Checking a url once isn't enough when we fetch it later, the dns answer
+ * can change in between and redirects can lead anywhere. The client used
+ * for member urls resolves hosts itself and only connects to addresses it
+ * checked, and every redirect goes through the same host checks again.
+ */
+struct GuardedResolver {
+    resolve_hosts: bool,
+}
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolve_hosts = self.resolve_hosts;
+
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = lookup_host((name.as_str(), 0)).await?.collect();
+
+            // Without resolving hosts there's no check to pin against
+            if resolve_hosts && addresses.iter().any(|address| is_private(address.ip())) {
+                return Err(UrlError::PrivateHost.to_string().into());
+            }
+
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn redirect_policy() -> redirect::Policy {
+    redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error("too many redirects");
+        }
+
+        match check_host(attempt.url()) {
+            Ok(_) => attempt.follow(),
+            Err(err) => attempt.error(err.to_string()),
+        }
+    })
+}
+
+pub fn guarded_client(resolve_hosts: bool) -> Client {
+    Client::builder()
+        .user_agent(concat!("petring/", env!("CARGO_PKG_VERSION")))
+        .dns_resolver(Arc::new(GuardedResolver { resolve_hosts }))
+        .redirect(redirect_policy())
+        .build()
+        .expect("Failed to build http client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(host: &str) -> Rule {
+        Rule {
+            id: None,
+            kind: UrlKind::Image,
+            action: RuleAction::Deny,
+            host: host.to_string(),
+        }
+    }
+
+    fn private(ip: &str) -> bool {
+        is_private(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn private_v4_ranges() {
+        assert!(private("127.0.0.1"));
+        assert!(private("10.1.2.3"));
+        assert!(private("192.168.0.1"));
+        assert!(private("169.254.169.254"));
+        assert!(private("0.0.0.0"));
+        assert!(!private("93.184.216.34"));
+    }
+
+    #[test]
+    fn carrier_grade_nat_is_private() {
+        assert!(private("100.64.0.1"));
+        assert!(private("100.127.255.255"));
+        assert!(!private("100.63.255.255"));
+        assert!(!private("100.128.0.0"));
+    }
+
+    #[test]
+    fn v4_mapped_v6_uses_the_v4_rules() {
+        assert!(private("::ffff:127.0.0.1"));
+        assert!(private("::ffff:10.0.0.1"));
+        assert!(private("::ffff:100.64.0.1"));
+        assert!(!private("::ffff:93.184.216.34"));
+    }
+
+    #[test]
+    fn unique_local_v6_is_private() {
+        assert!(private("fc00::1"));
+        assert!(private("fd12:3456:789a::1"));
+        assert!(private("fe80::1"));
+        assert!(private("::1"));
+        assert!(!private("fe00::1"));
+        assert!(!private("2606:4700::1111"));
+    }
+
+    #[test]
+    fn rules_match_whole_labels() {
+        let github = rule("github.com");
+        assert!(github.matches("github.com"));
+        assert!(github.matches("gist.github.com"));
+        assert!(!github.matches("githubusercontent.com"));
+        assert!(!github.matches("raw.githubusercontent.com"));
+        assert!(!github.matches("notgithub.com"));
+    }
+
+    fn canonical_of(url: &str) -> String {
+        canonical(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn canonical_drops_tracking_params() {
+        assert_eq!(
+            canonical_of("https://site.dev/?utm_source=x&UTM_Campaign=y&fbclid=z"),
+            "site.dev"
+        );
+        assert_eq!(
+            canonical_of("https://site.dev/page?ref=a&id=3&gclid=b&sort=new"),
+            "site.dev/page?id=3&sort=new"
+        );
+        // Only whole names count, `reference` isn't `ref`
+        assert_eq!(
+            canonical_of("https://site.dev/?reference=1"),
+            "site.dev?reference=1"
+        );
+    }
+
+    #[test]
+    fn canonical_ignores_scheme_case_fragment_and_trailing_slash() {
+        let expected = "site.dev/blog";
+        assert_eq!(canonical_of("HTTPS://Site.DEV/blog/"), expected);
+        assert_eq!(canonical_of("http://site.dev/blog//"), expected);
+        assert_eq!(canonical_of("https://site.dev./blog#top"), expected);
+        assert_ne!(canonical_of("https://site.dev/Blog"), expected);
+    }
+
+    #[test]
+    fn canonical_uses_punycode_for_idns() {
+        assert_eq!(
+            canonical_of("https://Bücher.example/"),
+            "xn--bcher-kva.example"
+        );
+        assert_eq!(
+            canonical_of("https://bücher.example"),
+            canonical_of("https://xn--bcher-kva.example/")
+        );
+    }
+
+    #[test]
+    fn canonical_keeps_only_non_default_ports() {
+        assert_eq!(canonical_of("https://site.dev:443/"), "site.dev");
+        assert_eq!(canonical_of("http://site.dev:80/"), "site.dev");
+        assert_eq!(canonical_of("https://site.dev:8443/"), "site.dev:8443");
+        // The scheme goes, so the default of the other one stays visible
+        assert_eq!(canonical_of("http://site.dev:443/"), "site.dev:443");
+    }
+}
@@ -0,0 +1,229 @@
+use super::database::{
+    ads,
+    entities::{Ads, UsernameAliasModel, UsernameAliases, Users},
+    username_aliases, users,
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, Set,
+    prelude::Expr,
+};
+use std::fmt;
+use unicode_normalization::UnicodeNormalization;
+
+/* Usernames
+ *
+ * Usernames end up in paths like `/user/{username}/next`, so they're kept to
+ * letters, digits, `_` and `-`. Spaces and dots are turned into `_` like they
+ * always were. Lookups go through `key`, which is what makes `Alice` and
+ * `alice` the same member.
+ *
+ * Old names are kept as aliases after a rename so embedded widgets keep
+ * working, and nobody else can take them.
+ */
+
+const MIN_LENGTH: usize = 2;
+const MAX_LENGTH: usize = 32;
+
+// Would clash with routes or look official
+const RESERVED: [&str; 24] = [
+    "admin",
+    "administrator",
+    "ad",
+    "ads",
+    "api",
+    "bot",
+    "buttons",
+    "delete",
+    "embed",
+    "get",
+    "mod",
+    "moderator",
+    "next",
+    "patch",
+    "petring",
+    "post",
+    "prev",
+    "random",
+    "ring",
+    "root",
+    "staff",
+    "system",
+    "user",
+    "widget",
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UsernameError {
+    TooShort,
+    TooLong,
+    Character(char),
+    Edge,
+    Reserved,
+}
+
+impl fmt::Display for UsernameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooShort => write!(f, "has to be at least {MIN_LENGTH} characters"),
+            Self::TooLong => write!(f, "can't be longer than {MAX_LENGTH} characters"),
+            Self::Character(c) => write!(f, "can't contain {c:?}"),
+            Self::Edge => write!(f, "has to start and end with a letter or digit"),
+            Self::Reserved => write!(f, "is reserved"),
+        }
+    }
+}
+
+// Returns the username as it should be stored
+pub fn validate(raw: &str) -> Result<String, UsernameError> {
+    let username: String = raw
+        .nfkc()
+        .collect::<String>()
+        .trim()
+        .replace([' ', '.'], "_");
+
+    let length = username.chars().count();
+    if length < MIN_LENGTH {
+        return Err(UsernameError::TooShort);
+    }
+
+    if length > MAX_LENGTH {
+        return Err(UsernameError::TooLong);
+    }
+
+    if let Some(c) = username
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || *c == '_' || *c == '-'))
+    {
+        return Err(UsernameError::Character(c));
+    }
+
+    let edges = [username.chars().next(), username.chars().last()];
+    if edges.into_iter().flatten().any(|c| !c.is_alphanumeric()) {
+        return Err(UsernameError::Edge);
+    }
+
+    if RESERVED.contains(&key(&username).as_str()) {
+        return Err(UsernameError::Reserved);
+    }
+
+    Ok(username)
+}
+
+// What usernames are compared by, stored in `users.username_key`
+pub fn key(username: &str) -> String {
+    username
+        .nfkc()
+        .collect::<String>()
+        .trim()
+        .replace([' ', '.'], "_")
+        .to_lowercase()
+}
+
+// `owner` can reuse their own names, e.g. renaming back to an old one
+pub async fn taken<C: ConnectionTrait>(
+    db: &C,
+    key: &str,
+    owner: Option<i32>,
+) -> Result<bool, DbErr> {
+    let mut current = Users::find().filter(users::Column::UsernameKey.eq(key));
+    let mut alias = UsernameAliases::find().filter(username_aliases::Column::UsernameKey.eq(key));
+
+    if let Some(owner) = owner {
+        current = current.filter(users::Column::Id.ne(owner));
+        alias = alias.filter(username_aliases::Column::UserId.ne(owner));
+    }
+
+    Ok(current.one(db).await?.is_some() || alias.one(db).await?.is_some())
+}
+
+// Call with the member as they were before and after the rename, in the
+// same transaction as the update
+pub async fn record_rename<C: ConnectionTrait>(
+    db: &C,
+    old: &users::Model,
+    new: &users::Model,
+) -> Result<(), DbErr> {
+    let new_key = new.username_key.as_str();
+
+    // Ads keep a copy of the name for lookups by username
+    Ads::update_many()
+        .col_expr(ads::Column::Username, Expr::value(new.username.clone()))
+        .filter(ads::Column::DiscordId.eq(old.discord_id))
+        .exec(db)
+        .await?;
+
+    UsernameAliases::delete_many()
+        .filter(username_aliases::Column::UserId.eq(old.id))
+        .filter(username_aliases::Column::UsernameKey.eq(new_key))
+        .exec(db)
+        .await?;
+
+    // Only the case changed, the old links already work
+    if old.username_key == new_key {
+        return Ok(());
+    }
+
+    UsernameAliasModel {
+        user_id: Set(old.id),
+        username: Set(old.username.clone()),
+        username_key: Set(old.username_key.clone()),
+        created_at: Set(Utc::now().to_rfc3339()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_names_are_caught_after_normalizing() {
+        assert_eq!(validate("admin"), Err(UsernameError::Reserved));
+        assert_eq!(validate("Admin"), Err(UsernameError::Reserved));
+        // Fullwidth letters fold to ascii under NFKC
+        assert_eq!(validate("ＡＤＭＩＮ"), Err(UsernameError::Reserved));
+        assert_eq!(validate(" petring "), Err(UsernameError::Reserved));
+        assert_eq!(validate("admins"), Ok("admins".to_string()));
+    }
+
+    #[test]
+    fn edges_have_to_be_letters_or_digits() {
+        assert_eq!(validate("_alice"), Err(UsernameError::Edge));
+        assert_eq!(validate("alice-"), Err(UsernameError::Edge));
+        // Dots and spaces become `_`, so they can't sit at the edges either
+        assert_eq!(validate(".alice"), Err(UsernameError::Edge));
+        assert_eq!(validate("alice bob"), Ok("alice_bob".to_string()));
+        assert_eq!(validate("  alice  "), Ok("alice".to_string()));
+        assert_eq!(validate("al!ce"), Err(UsernameError::Character('!')));
+    }
+
+    #[test]
+    fn length_counts_characters() {
+        assert_eq!(validate("a"), Err(UsernameError::TooShort));
+        assert_eq!(validate("ab"), Ok("ab".to_string()));
+        assert_eq!(
+            validate(&"a".repeat(MAX_LENGTH)),
+            Ok("a".repeat(MAX_LENGTH))
+        );
+        assert_eq!(
+            validate(&"a".repeat(MAX_LENGTH + 1)),
+            Err(UsernameError::TooLong)
+        );
+        // Two characters, four bytes
+        assert_eq!(validate("éé"), Ok("éé".to_string()));
+        // Whitespace around the name doesn't count towards it
+        assert_eq!(validate(" a "), Err(UsernameError::TooShort));
+    }
+
+    #[test]
+    fn keys_ignore_case_and_width() {
+        assert_eq!(key("Alice"), key("alice"));
+        assert_eq!(key("ａｌｉｃｅ"), key("alice"));
+        assert_eq!(key("alice.bob"), key("Alice Bob"));
+    }
+}
@@ -0,0 +1,68 @@
+"use strict";
+
+// Usage:
+// <script src="https://{api}/widget.js" data-username="{username}"></script>
+//
+// Optionally pass data-target="#some-element" to render somewhere other than
+// right after the script tag.
+
+(function () {
+  const script = document.currentScript;
+  if (!script) {
+    return;
+  }
+
+  const username = script.dataset.username;
+  if (!username) {
+    console.error("petring: missing data-username on widget script");
+    return;
+  }
+
+  const api_url = script.src.replace(/\/widget\.js(\?.*)?$/, "");
+
+  function makeLink(label, user, rel) {
+    const link = document.createElement("a");
+    link.className = `petring-${rel}`;
+    link.innerText = label;
+
+    if (user) {
+      link.href = user.url;
+      link.title = user.username;
+    }
+
+    return link;
+  }
+
+  function render(data) {
+    const nav = document.createElement("nav");
+    nav.className = "petring-widget";
+
+    nav.appendChild(makeLink("← prev", data.prev, "prev"));
+    nav.appendChild(document.createTextNode(" | "));
+    nav.appendChild(makeLink("random", data.random, "random"));
+    nav.appendChild(document.createTextNode(" | "));
+    nav.appendChild(makeLink("next →", data.next, "next"));
+
+    const target = script.dataset.target
+      ? document.querySelector(script.dataset.target)
+      : null;
+
+    if (target) {
+      target.appendChild(nav);
+    } else {
+      script.insertAdjacentElement("afterend", nav);
+    }
+  }
+
+  fetch(`${api_url}/get/user/${encodeURIComponent(username)}/neighbors`)
+    .then((response) => {
+      if (!response.ok) {
+        return Promise.reject(response);
+      }
+      return response.json();
+    })
+    .then(render)
+    .catch((error) => {
+      console.error("petring: couldn't load ring neighbors", error);
+    });
+})();
@@ -12,12 +12,24 @@ function flipCoin() {
 
 let api_url;
 
+// Embedders pick the slot with `/petads?format=banner`, leaving it out
+// serves any format
+const format = new URLSearchParams(window.location.search).get("format");
+
+// Stored images and click links come back relative when the api has no
+// public url configured
+function fromApi(url) {
+  return url.startsWith("/") ? `${api_url}${url}` : url;
+}
+
 async function getRandomAd() {
   const image_element = document.getElementById("image");
   const image_link_element = document.getElementById("image-link");
   const promo_link_element = document.getElementById("promo-link");
 
-  fetch(`${api_url}/get/random-ad`)
+  const query = format ? `?format=${encodeURIComponent(format)}` : "";
+
+  fetch(`${api_url}/get/random-ad${query}`)
     .then((response) => {
       if (!response.ok) {
         return Promise.reject(response);
@@ -28,9 +40,9 @@ async function getRandomAd() {
       let ad_data = data;
 
       document.title = data.username;
-      image_element.src = data.image_url;
+      image_element.src = fromApi(data.image_url);
       image_element.alt = data.username;
-      image_link_element.href = data.ad_url;
+      image_link_element.href = fromApi(data.click_url ?? data.ad_url);
       promo_link_element.innerText = `from ${data.username} (click here for more info about PetAds)`;
     })
     .catch(async (error) => {
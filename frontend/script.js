@@ -77,12 +77,27 @@ async function getUptimes() {
     });
 }
 
+// The list is paged, keep following the cursor until every member is in
+async function fetchAllUsers() {
+  let users = [];
+  let cursor = null;
+  do {
+    let url = `${api_url}/get/users?limit=200`;
+    if (cursor) {
+      url += `&cursor=${encodeURIComponent(cursor)}`;
+    }
+    const response = await fetch(url);
+    const data = await response.json();
+    users = users.concat(data.users);
+    cursor = data.next_cursor;
+  } while (cursor);
+  return users;
+}
+
 async function genApiLinks() {
-  fetch(`${api_url}/get/users`)
-    .then((response) => response.json())
-    .then((data) => {
-      let users = data;
-      users.users.forEach((user) => {
+  fetchAllUsers()
+    .then((users) => {
+      users.forEach((user) => {
         makeLink(user.username, user.url);
       });
       calculateRotations();
@@ -8,8 +8,46 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20250922_025851_create_table_users::Migration),
             Box::new(m20251001_135745_create_table_ads::Migration),
+            Box::new(m20261017_090000_add_position_to_users::Migration),
+            Box::new(m20261017_100000_add_hidden_to_users::Migration),
+            Box::new(m20261017_110000_create_table_link_health::Migration),
+            Box::new(m20261017_120000_add_backlinks_to_users::Migration),
+            Box::new(m20261017_130000_create_table_rings::Migration),
+            Box::new(m20261017_140000_add_profile_to_users::Migration),
+            Box::new(m20261017_150000_create_users_fts::Migration),
+            Box::new(m20261017_160000_add_status_to_users_and_ads::Migration),
+            Box::new(m20261017_170000_create_table_audit_log::Migration),
+            Box::new(m20261017_180000_create_table_bans::Migration),
+            Box::new(m20261017_190000_create_table_url_rules::Migration),
+            Box::new(m20261017_200000_add_canonical_urls::Migration),
+            Box::new(m20261017_210000_add_username_key_to_users::Migration),
+            Box::new(m20261017_220000_create_table_username_aliases::Migration),
+            Box::new(m20261017_230000_add_image_to_ads::Migration),
+            Box::new(m20261017_240000_add_rotation_to_ads::Migration),
+            Box::new(m20261017_250000_create_table_ad_daily_stats::Migration),
+            Box::new(m20261017_260000_add_format_to_ads::Migration),
+            Box::new(m20261017_270000_add_schedule_to_ads::Migration),
         ]
     }
 }
 mod m20250922_025851_create_table_users;
 mod m20251001_135745_create_table_ads;
+mod m20261017_090000_add_position_to_users;
+mod m20261017_100000_add_hidden_to_users;
+mod m20261017_110000_create_table_link_health;
+mod m20261017_120000_add_backlinks_to_users;
+mod m20261017_130000_create_table_rings;
+mod m20261017_140000_add_profile_to_users;
+mod m20261017_150000_create_users_fts;
+mod m20261017_160000_add_status_to_users_and_ads;
+mod m20261017_170000_create_table_audit_log;
+mod m20261017_180000_create_table_bans;
+mod m20261017_190000_create_table_url_rules;
+mod m20261017_200000_add_canonical_urls;
+mod m20261017_210000_add_username_key_to_users;
+mod m20261017_220000_create_table_username_aliases;
+mod m20261017_230000_add_image_to_ads;
+mod m20261017_240000_add_rotation_to_ads;
+mod m20261017_250000_create_table_ad_daily_stats;
+mod m20261017_260000_add_format_to_ads;
+mod m20261017_270000_add_schedule_to_ads;
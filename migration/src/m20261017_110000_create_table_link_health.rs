@@ -0,0 +1,81 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LinkHealth::Table)
+                    .if_not_exists()
+                    .col(pk_auto(LinkHealth::Id))
+                    .col(integer_uniq(LinkHealth::UserId).not_null())
+                    .col(integer_null(LinkHealth::StatusCode))
+                    .col(integer_null(LinkHealth::LatencyMs))
+                    .col(string(LinkHealth::Error).not_null().default(""))
+                    .col(string(LinkHealth::LastCheckedAt).not_null().default(""))
+                    .col(string(LinkHealth::LastUpAt).not_null().default(""))
+                    .col(string(LinkHealth::DownSince).not_null().default(""))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("link_health_user_id_fk")
+                            .from(LinkHealth::Table, LinkHealth::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("link_health_down_since_idx")
+                    .table(LinkHealth::Table)
+                    .col(LinkHealth::DownSince)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("link_health_down_since_idx")
+                    .table(LinkHealth::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(LinkHealth::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum LinkHealth {
+    Table,
+    Id,
+    UserId,
+    StatusCode,
+    LatencyMs,
+    Error,
+    LastCheckedAt,
+    LastUpAt,
+    DownSince,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
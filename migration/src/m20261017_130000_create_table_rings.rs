@@ -0,0 +1,140 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::sqlx::types::chrono::Utc};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Rings::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Rings::Id))
+                    .col(string_uniq(Rings::Slug).not_null())
+                    .col(string(Rings::Name).not_null())
+                    .col(string(Rings::Description).not_null().default(""))
+                    .col(
+                        string(Rings::CreatedAt)
+                            .not_null()
+                            .default(Utc::now().to_rfc3339()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RingMembers::Table)
+                    .if_not_exists()
+                    .col(pk_auto(RingMembers::Id))
+                    .col(integer(RingMembers::RingId).not_null())
+                    .col(integer(RingMembers::UserId).not_null())
+                    .col(integer(RingMembers::Position).not_null().default(0))
+                    .col(
+                        string(RingMembers::CreatedAt)
+                            .not_null()
+                            .default(Utc::now().to_rfc3339()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("ring_members_ring_id_fk")
+                            .from(RingMembers::Table, RingMembers::RingId)
+                            .to(Rings::Table, Rings::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("ring_members_user_id_fk")
+                            .from(RingMembers::Table, RingMembers::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("ring_members_ring_id_user_id_idx")
+                    .table(RingMembers::Table)
+                    .col(RingMembers::RingId)
+                    .col(RingMembers::UserId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("ring_members_position_idx")
+                    .table(RingMembers::Table)
+                    .col(RingMembers::RingId)
+                    .col(RingMembers::Position)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("ring_members_position_idx")
+                    .table(RingMembers::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("ring_members_ring_id_user_id_idx")
+                    .table(RingMembers::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(RingMembers::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Rings::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Rings {
+    Table,
+    Id,
+    Slug,
+    Name,
+    Description,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum RingMembers {
+    Table,
+    Id,
+    RingId,
+    UserId,
+    Position,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
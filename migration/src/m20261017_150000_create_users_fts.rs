@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// SQLite has no builder for virtual tables or triggers, so this one is raw SQL.
+// `users_fts` is an external content table over `users`, the triggers keep it
+// in sync with the searchable columns.
+const UP: &[&str] = &[
+    r#"CREATE VIRTUAL TABLE IF NOT EXISTS "users_fts" USING fts5(
+        "username", "description", "tags",
+        content = 'users',
+        content_rowid = 'id',
+        tokenize = 'unicode61 remove_diacritics 2'
+    )"#,
+    r#"CREATE TRIGGER IF NOT EXISTS "users_fts_insert" AFTER INSERT ON "users" BEGIN
+        INSERT INTO "users_fts" ("rowid", "username", "description", "tags")
+        VALUES (new."id", new."username", new."description", new."tags");
+    END"#,
+    r#"CREATE TRIGGER IF NOT EXISTS "users_fts_delete" AFTER DELETE ON "users" BEGIN
+        INSERT INTO "users_fts" ("users_fts", "rowid", "username", "description", "tags")
+        VALUES ('delete', old."id", old."username", old."description", old."tags");
+    END"#,
+    r#"CREATE TRIGGER IF NOT EXISTS "users_fts_update"
+        AFTER UPDATE OF "username", "description", "tags" ON "users" BEGIN
+        INSERT INTO "users_fts" ("users_fts", "rowid", "username", "description", "tags")
+        VALUES ('delete', old."id", old."username", old."description", old."tags");
+        INSERT INTO "users_fts" ("rowid", "username", "description", "tags")
+        VALUES (new."id", new."username", new."description", new."tags");
+    END"#,
+    // Index everyone that joined before search existed
+    r#"INSERT INTO "users_fts" ("users_fts") VALUES ('rebuild')"#,
+];
+
+const DOWN: &[&str] = &[
+    r#"DROP TRIGGER IF EXISTS "users_fts_update""#,
+    r#"DROP TRIGGER IF EXISTS "users_fts_delete""#,
+    r#"DROP TRIGGER IF EXISTS "users_fts_insert""#,
+    r#"DROP TABLE IF EXISTS "users_fts""#,
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        for statement in UP {
+            db.execute_unprepared(statement).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        for statement in DOWN {
+            db.execute_unprepared(statement).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// `users` and `ads` get the exact same moderation columns, so the steps are
+// shared and run once per table.
+async fn add_status(manager: &SchemaManager<'_>, table: Moderation) -> Result<(), DbErr> {
+    let columns = [
+        string(Moderation::Status)
+            .not_null()
+            .default("pending")
+            .to_owned(),
+        string(Moderation::StatusReason)
+            .not_null()
+            .default("")
+            .to_owned(),
+        big_integer_null(Moderation::ModeratedBy).to_owned(),
+        string(Moderation::ModeratedAt)
+            .not_null()
+            .default("")
+            .to_owned(),
+    ];
+
+    for mut column in columns {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table)
+                    .add_column(&mut column)
+                    .to_owned(),
+            )
+            .await?;
+    }
+
+    manager
+        .exec_stmt(
+            Query::update()
+                .table(table)
+                .value(Moderation::Status, "approved")
+                .and_where(Expr::col(Moderation::Verified).eq(true))
+                .to_owned(),
+        )
+        .await?;
+
+    manager
+        .create_index(
+            Index::create()
+                .name(format!("{}_status_idx", table.to_string()))
+                .table(table)
+                .col(Moderation::Status)
+                .col(Moderation::CreatedAt)
+                .to_owned(),
+        )
+        .await?;
+
+    manager
+        .alter_table(
+            Table::alter()
+                .table(table)
+                .drop_column(Moderation::Verified)
+                .to_owned(),
+        )
+        .await
+}
+
+async fn drop_status(manager: &SchemaManager<'_>, table: Moderation) -> Result<(), DbErr> {
+    manager
+        .alter_table(
+            Table::alter()
+                .table(table)
+                .add_column(boolean(Moderation::Verified).not_null().default(false))
+                .to_owned(),
+        )
+        .await?;
+
+    manager
+        .exec_stmt(
+            Query::update()
+                .table(table)
+                .value(Moderation::Verified, true)
+                .and_where(Expr::col(Moderation::Status).eq("approved"))
+                .to_owned(),
+        )
+        .await?;
+
+    manager
+        .drop_index(
+            Index::drop()
+                .name(format!("{}_status_idx", table.to_string()))
+                .table(table)
+                .to_owned(),
+        )
+        .await?;
+
+    for column in [
+        Moderation::Status,
+        Moderation::StatusReason,
+        Moderation::ModeratedBy,
+        Moderation::ModeratedAt,
+    ] {
+        manager
+            .alter_table(Table::alter().table(table).drop_column(column).to_owned())
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        add_status(manager, Moderation::Users).await?;
+        add_status(manager, Moderation::Ads).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_status(manager, Moderation::Ads).await?;
+        drop_status(manager, Moderation::Users).await
+    }
+}
+
+#[derive(DeriveIden, Clone, Copy)]
+enum Moderation {
+    Users,
+    Ads,
+    Verified,
+    Status,
+    StatusReason,
+    ModeratedBy,
+    ModeratedAt,
+    CreatedAt,
+}
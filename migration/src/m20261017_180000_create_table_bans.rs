@@ -0,0 +1,68 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Bans::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Bans::Id))
+                    .col(string(Bans::Kind).not_null())
+                    .col(string(Bans::Value).not_null())
+                    .col(string(Bans::Reason).not_null().default(""))
+                    .col(string(Bans::ExpiresAt).not_null().default(""))
+                    .col(big_integer_null(Bans::CreatedBy))
+                    .col(string(Bans::CreatedAt).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("bans_kind_value_idx")
+                    .table(Bans::Table)
+                    .col(Bans::Kind)
+                    .col(Bans::Value)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("bans_kind_value_idx")
+                    .table(Bans::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Bans::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Bans {
+    Table,
+    Id,
+    Kind,
+    Value,
+    Reason,
+    ExpiresAt,
+    CreatedBy,
+    CreatedAt,
+}
@@ -0,0 +1,66 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UrlRules::Table)
+                    .if_not_exists()
+                    .col(pk_auto(UrlRules::Id))
+                    .col(string(UrlRules::Kind).not_null())
+                    .col(string(UrlRules::Action).not_null())
+                    .col(string(UrlRules::Host).not_null())
+                    .col(big_integer_null(UrlRules::CreatedBy))
+                    .col(string(UrlRules::CreatedAt).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("url_rules_kind_host_idx")
+                    .table(UrlRules::Table)
+                    .col(UrlRules::Kind)
+                    .col(UrlRules::Host)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("url_rules_kind_host_idx")
+                    .table(UrlRules::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(UrlRules::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UrlRules {
+    Table,
+    Id,
+    Kind,
+    Action,
+    Host,
+    CreatedBy,
+    CreatedAt,
+}
@@ -0,0 +1,217 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::ConnectionTrait};
+use std::collections::HashMap;
+use url::Url;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Copy of `url_policy::canonical` in the api as it was when this migration
+// was written, so later changes there don't change what this one does.
+const TRACKING_PARAMS: [&str; 10] = [
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "ref", "ref_src",
+];
+
+fn canonical(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url.trim()) else {
+        // Nothing we can do with these, compare them as they are
+        return url.trim().to_lowercase();
+    };
+
+    let mut canonical = parsed
+        .host_str()
+        .unwrap_or_default()
+        .trim_end_matches('.')
+        .to_string();
+
+    if let Some(port) = parsed.port() {
+        canonical.push_str(&format!(":{port}"));
+    }
+
+    canonical.push_str(parsed.path().trim_end_matches('/'));
+
+    let query: Vec<&str> = parsed
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| {
+            let name = pair
+                .split_once('=')
+                .map_or(*pair, |(name, _)| name)
+                .to_ascii_lowercase();
+            !name.is_empty()
+                && !name.starts_with("utm_")
+                && !TRACKING_PARAMS.contains(&name.as_str())
+        })
+        .collect();
+
+    if !query.is_empty() {
+        canonical.push('?');
+        canonical.push_str(&query.join("&"));
+    }
+
+    canonical
+}
+
+/* Fills the canonical column for every existing row. When two rows end up
+ * with the same canonical url the oldest one keeps it, the others get their
+ * id appended (`#` can't appear otherwise, fragments are dropped) so the
+ * unique index can still be created. Those are printed so they can be
+ * sorted out by hand.
+ */
+async fn backfill<T>(
+    manager: &SchemaManager<'_>,
+    table: T,
+    url: T,
+    canonical_column: T,
+) -> Result<(), DbErr>
+where
+    T: Iden + Copy + 'static,
+{
+    let db = manager.get_connection();
+    let backend = db.get_database_backend();
+
+    let rows = db
+        .query_all(
+            backend.build(
+                Query::select()
+                    .column(Alias::new("id"))
+                    .column(url)
+                    .from(table)
+                    .order_by(Alias::new("id"), Order::Asc),
+            ),
+        )
+        .await?;
+
+    let mut seen: HashMap<String, i32> = HashMap::new();
+    for row in rows {
+        let id: i32 = row.try_get("", "id")?;
+        let raw: String = row.try_get("", &url.to_string())?;
+        let mut value = canonical(&raw);
+
+        if let Some(first) = seen.get(&value) {
+            println!(
+                "{}: row {id} ({raw}) has the same url as row {first}, stored as {value}#{id}",
+                table.to_string(),
+            );
+            value = format!("{value}#{id}");
+        } else {
+            seen.insert(value.clone(), id);
+        }
+
+        db.execute(
+            backend.build(
+                Query::update()
+                    .table(table)
+                    .value(canonical_column, value)
+                    .and_where(Expr::col(Alias::new("id")).eq(id)),
+            ),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(string(Users::UrlCanonical).not_null().default(""))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Ads::Table)
+                    .add_column(string(Ads::ImageUrlCanonical).not_null().default(""))
+                    .to_owned(),
+            )
+            .await?;
+
+        backfill(manager, Users::Table, Users::Url, Users::UrlCanonical).await?;
+        backfill(manager, Ads::Table, Ads::ImageUrl, Ads::ImageUrlCanonical).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("users_url_canonical_idx")
+                    .table(Users::Table)
+                    .col(Users::UrlCanonical)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("ads_image_url_canonical_idx")
+                    .table(Ads::Table)
+                    .col(Ads::ImageUrlCanonical)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("ads_image_url_canonical_idx")
+                    .table(Ads::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("users_url_canonical_idx")
+                    .table(Users::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Ads::Table)
+                    .drop_column(Ads::ImageUrlCanonical)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::UrlCanonical)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden, Clone, Copy)]
+enum Users {
+    Table,
+    Url,
+    UrlCanonical,
+}
+
+#[derive(DeriveIden, Clone, Copy)]
+enum Ads {
+    Table,
+    ImageUrl,
+    ImageUrlCanonical,
+}
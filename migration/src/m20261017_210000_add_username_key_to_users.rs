@@ -0,0 +1,115 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::ConnectionTrait};
+use std::collections::HashMap;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Same as `username::key` in the api when this was written
+fn key(username: &str) -> String {
+    username
+        .nfkc()
+        .collect::<String>()
+        .trim()
+        .replace([' ', '.'], "_")
+        .to_lowercase()
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(string(Users::UsernameKey).not_null().default(""))
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        let backend = db.get_database_backend();
+
+        let rows = db
+            .query_all(
+                backend.build(
+                    Query::select()
+                        .columns([Users::Id, Users::Username])
+                        .from(Users::Table)
+                        .order_by(Users::Id, Order::Asc),
+                ),
+            )
+            .await?;
+
+        // Members that only differ by case keep their names, the oldest one
+        // gets the key and the others are printed to be renamed by hand.
+        let mut seen: HashMap<String, i32> = HashMap::new();
+        for row in rows {
+            let id: i32 = row.try_get("", "id")?;
+            let username: String = row.try_get("", "username")?;
+            let mut value = key(&username);
+
+            if let Some(first) = seen.get(&value) {
+                println!(
+                    "users: {username} (row {id}) clashes with row {first}, stored as {value}#{id}"
+                );
+                value = format!("{value}#{id}");
+            } else {
+                seen.insert(value.clone(), id);
+            }
+
+            db.execute(
+                backend.build(
+                    Query::update()
+                        .table(Users::Table)
+                        .value(Users::UsernameKey, value)
+                        .and_where(Expr::col(Users::Id).eq(id)),
+                ),
+            )
+            .await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("users_username_key_idx")
+                    .table(Users::Table)
+                    .col(Users::UsernameKey)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("users_username_key_idx")
+                    .table(Users::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::UsernameKey)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+    Username,
+    UsernameKey,
+}
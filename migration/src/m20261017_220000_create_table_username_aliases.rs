@@ -0,0 +1,79 @@
+use sea_orm_migration::{prelude::*, schema::*, sea_orm::sqlx::types::chrono::Utc};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UsernameAliases::Table)
+                    .if_not_exists()
+                    .col(pk_auto(UsernameAliases::Id))
+                    .col(integer(UsernameAliases::UserId).not_null())
+                    .col(string(UsernameAliases::Username).not_null())
+                    .col(string_uniq(UsernameAliases::UsernameKey).not_null())
+                    .col(
+                        string(UsernameAliases::CreatedAt)
+                            .not_null()
+                            .default(Utc::now().to_rfc3339()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("username_aliases_user_id_fk")
+                            .from(UsernameAliases::Table, UsernameAliases::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("username_aliases_user_id_idx")
+                    .table(UsernameAliases::Table)
+                    .col(UsernameAliases::UserId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("username_aliases_user_id_idx")
+                    .table(UsernameAliases::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(UsernameAliases::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UsernameAliases {
+    Table,
+    Id,
+    UserId,
+    Username,
+    UsernameKey,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
@@ -0,0 +1,62 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Ads submitted before this keep hotlinking `image_url` until they're edited
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Ads::Table)
+                    .add_column(string(Ads::ImageHash).not_null().default(""))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Ads::Table)
+                    .add_column(integer(Ads::ImageWidth).not_null().default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Ads::Table)
+                    .add_column(integer(Ads::ImageHeight).not_null().default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Ads::ImageHeight, Ads::ImageWidth, Ads::ImageHash] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Ads::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Ads {
+    Table,
+    ImageHash,
+    ImageWidth,
+    ImageHeight,
+}
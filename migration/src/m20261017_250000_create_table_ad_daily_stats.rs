@@ -0,0 +1,78 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AdDailyStats::Table)
+                    .if_not_exists()
+                    .col(pk_auto(AdDailyStats::Id))
+                    .col(integer(AdDailyStats::AdId).not_null())
+                    // UTC, YYYY-MM-DD
+                    .col(string(AdDailyStats::Day).not_null())
+                    .col(big_integer(AdDailyStats::Impressions).not_null().default(0))
+                    .col(big_integer(AdDailyStats::Clicks).not_null().default(0))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("ad_daily_stats_ad_id_fk")
+                            .from(AdDailyStats::Table, AdDailyStats::AdId)
+                            .to(Ads::Table, Ads::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("ad_daily_stats_ad_id_day_idx")
+                    .table(AdDailyStats::Table)
+                    .col(AdDailyStats::AdId)
+                    .col(AdDailyStats::Day)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("ad_daily_stats_ad_id_day_idx")
+                    .table(AdDailyStats::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(AdDailyStats::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AdDailyStats {
+    Table,
+    Id,
+    AdId,
+    Day,
+    Impressions,
+    Clicks,
+}
+
+#[derive(DeriveIden)]
+enum Ads {
+    Table,
+    Id,
+}
@@ -0,0 +1,130 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Members can have one ad per format, so the unique `username`, `discord_id`
+// and `ad_url` columns have to go. SQLite can't drop column constraints, so
+// the table is rebuilt. Dropping `ads` takes `ad_daily_stats` with it through
+// the cascade, so the stats are set aside and put back afterwards.
+//
+// Existing ads get the format their image fits, anything else counts as a
+// button like before. Statuses are left alone: ads that were never measured
+// have 0x0 here, the image backfill sorts them out once it has the sizes.
+const UP: &[&str] = &[
+    r#"CREATE TABLE "ads_new" (
+        "id" integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+        "username" varchar NOT NULL,
+        "discord_id" integer NOT NULL,
+        "format" varchar NOT NULL DEFAULT 'button',
+        "image_url" varchar NOT NULL,
+        "ad_url" varchar NOT NULL,
+        "created_at" varchar NOT NULL,
+        "edited_at" varchar NOT NULL DEFAULT '',
+        "verified_at" varchar NOT NULL DEFAULT '',
+        "status" varchar NOT NULL DEFAULT 'pending',
+        "status_reason" varchar NOT NULL DEFAULT '',
+        "moderated_by" bigint NULL,
+        "moderated_at" varchar NOT NULL DEFAULT '',
+        "image_url_canonical" varchar NOT NULL DEFAULT '',
+        "image_hash" varchar NOT NULL DEFAULT '',
+        "image_width" integer NOT NULL DEFAULT 0,
+        "image_height" integer NOT NULL DEFAULT 0,
+        "weight" integer NOT NULL DEFAULT 1,
+        "impressions" bigint NOT NULL DEFAULT 0
+    )"#,
+    r#"INSERT INTO "ads_new" SELECT
+        "id", "username", "discord_id",
+        CASE
+            WHEN "image_width" = 468 AND "image_height" = 60 THEN 'banner'
+            WHEN "image_width" > 0 AND "image_width" = "image_height" THEN 'square'
+            ELSE 'button'
+        END,
+        "image_url", "ad_url", "created_at", "edited_at", "verified_at", "status",
+        "status_reason", "moderated_by", "moderated_at", "image_url_canonical",
+        "image_hash", "image_width", "image_height", "weight", "impressions"
+    FROM "ads""#,
+    r#"CREATE TABLE "ad_daily_stats_old" AS SELECT * FROM "ad_daily_stats""#,
+    r#"DELETE FROM "ad_daily_stats""#,
+    r#"DROP TABLE "ads""#,
+    r#"ALTER TABLE "ads_new" RENAME TO "ads""#,
+    r#"INSERT INTO "ad_daily_stats" SELECT * FROM "ad_daily_stats_old""#,
+    r#"DROP TABLE "ad_daily_stats_old""#,
+    r#"CREATE INDEX "ads_username_idx" ON "ads" ("username")"#,
+    r#"CREATE UNIQUE INDEX "ads_url_idx" ON "ads" ("image_url")"#,
+    r#"CREATE UNIQUE INDEX "ads_discord_id_format_idx" ON "ads" ("discord_id", "format")"#,
+    r#"CREATE INDEX "ads_created_at_idx" ON "ads" ("created_at")"#,
+    r#"CREATE INDEX "ads_edited_at_idx" ON "ads" ("edited_at")"#,
+    r#"CREATE INDEX "ads_verified_at_idx" ON "ads" ("verified_at")"#,
+    r#"CREATE INDEX "ads_status_idx" ON "ads" ("status", "created_at")"#,
+    r#"CREATE UNIQUE INDEX "ads_image_url_canonical_idx" ON "ads" ("image_url_canonical")"#,
+];
+
+// Only the oldest ad of each member survives going back
+const DOWN: &[&str] = &[
+    r#"DELETE FROM "ads" WHERE "id" NOT IN (
+        SELECT MIN("id") FROM "ads" GROUP BY "discord_id"
+    )"#,
+    r#"CREATE TABLE "ads_old" (
+        "id" integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+        "username" varchar NOT NULL UNIQUE,
+        "discord_id" integer NOT NULL UNIQUE,
+        "image_url" varchar NOT NULL UNIQUE,
+        "ad_url" varchar NOT NULL UNIQUE,
+        "created_at" varchar NOT NULL,
+        "edited_at" varchar NOT NULL DEFAULT '',
+        "verified_at" varchar NOT NULL DEFAULT '',
+        "status" varchar NOT NULL DEFAULT 'pending',
+        "status_reason" varchar NOT NULL DEFAULT '',
+        "moderated_by" bigint NULL,
+        "moderated_at" varchar NOT NULL DEFAULT '',
+        "image_url_canonical" varchar NOT NULL DEFAULT '',
+        "image_hash" varchar NOT NULL DEFAULT '',
+        "image_width" integer NOT NULL DEFAULT 0,
+        "image_height" integer NOT NULL DEFAULT 0,
+        "weight" integer NOT NULL DEFAULT 1,
+        "impressions" bigint NOT NULL DEFAULT 0
+    )"#,
+    r#"INSERT INTO "ads_old" SELECT
+        "id", "username", "discord_id", "image_url", "ad_url", "created_at",
+        "edited_at", "verified_at", "status", "status_reason", "moderated_by",
+        "moderated_at", "image_url_canonical", "image_hash", "image_width",
+        "image_height", "weight", "impressions"
+    FROM "ads""#,
+    r#"CREATE TABLE "ad_daily_stats_old" AS SELECT * FROM "ad_daily_stats""#,
+    r#"DELETE FROM "ad_daily_stats""#,
+    r#"DROP TABLE "ads""#,
+    r#"ALTER TABLE "ads_old" RENAME TO "ads""#,
+    r#"INSERT INTO "ad_daily_stats" SELECT * FROM "ad_daily_stats_old"
+        WHERE "ad_id" IN (SELECT "id" FROM "ads")"#,
+    r#"DROP TABLE "ad_daily_stats_old""#,
+    r#"CREATE UNIQUE INDEX "ads_username_idx" ON "ads" ("username")"#,
+    r#"CREATE UNIQUE INDEX "ads_url_idx" ON "ads" ("image_url")"#,
+    r#"CREATE UNIQUE INDEX "ads_discord_id_idx" ON "ads" ("discord_id")"#,
+    r#"CREATE INDEX "ads_created_at_idx" ON "ads" ("created_at")"#,
+    r#"CREATE INDEX "ads_edited_at_idx" ON "ads" ("edited_at")"#,
+    r#"CREATE INDEX "ads_verified_at_idx" ON "ads" ("verified_at")"#,
+    r#"CREATE INDEX "ads_status_idx" ON "ads" ("status", "created_at")"#,
+    r#"CREATE UNIQUE INDEX "ads_image_url_canonical_idx" ON "ads" ("image_url_canonical")"#,
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        for statement in UP {
+            db.execute_unprepared(statement).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        for statement in DOWN {
+            db.execute_unprepared(statement).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // RFC 3339 in UTC, empty means no limit on that side
+        for column in [Ads::StartsAt, Ads::EndsAt] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Ads::Table)
+                        .add_column(string(column).not_null().default(""))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Ads::EndsAt, Ads::StartsAt] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Ads::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Ads {
+    Table,
+    StartsAt,
+    EndsAt,
+}